@@ -0,0 +1,17 @@
+-- Baseline schema for accounts. Databases set up by hand before this
+-- migration existed already have it, hence the existence checks.
+DO $$
+BEGIN
+    CREATE TYPE user_role AS ENUM ('ADMIN', 'CLIENT');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS users (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role user_role NOT NULL DEFAULT 'CLIENT',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,29 @@
+-- Security-relevant authentication events. Rows are never updated or
+-- deleted, and outlive the accounts they mention.
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    reason TEXT,
+    user_id UUID,
+    username TEXT,
+    actor_id UUID,
+    ip TEXT,
+    user_agent TEXT,
+    details JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_user ON audit_log (user_id, created_at);
+CREATE INDEX idx_audit_log_event ON audit_log (event_type, created_at);
+CREATE INDEX idx_audit_log_created ON audit_log (created_at);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
@@ -0,0 +1,13 @@
+-- Refresh token families issued before sessions were tracked have no
+-- session row, so they would disappear from the session list and could not
+-- be revoked one by one. Give every family that can still be refreshed a
+-- session. Where the login came from was never recorded.
+INSERT INTO sessions (id, user_id, user_agent, ip, created_at, last_used_at)
+SELECT family_id, user_id, NULL, 'unknown', MIN(created_at), MAX(created_at)
+FROM refresh_tokens
+WHERE family_id IN (
+    SELECT family_id FROM refresh_tokens
+    WHERE used_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+)
+GROUP BY family_id, user_id
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::ApiError,
+    scopes,
+    secrets::{generate_token, hash_token},
+    user::UserRole,
+};
+
+const KEY_PREFIX: &str = "watt_";
+/// Characters of the key kept in clear so users can tell their keys apart.
+const DISPLAY_PREFIX_LEN: usize = 12;
+/// Longest lifetime a key can be created with, in days.
+pub const MAX_EXPIRES_IN_DAYS: i64 = 3650;
+
+/// A personal API key as shown to its owner; the key itself is never stored.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// The account an API key acts for.
+#[derive(Debug, sqlx::FromRow)]
+pub struct KeyOwner {
+    pub user_id: Uuid,
+    pub role: UserRole,
+    pub scopes: Vec<String>,
+}
+
+impl KeyOwner {
+    /// Key scopes still covered by the owner's role, so a demoted user's
+    /// keys lose what the role no longer grants.
+    pub fn effective_scopes(&self) -> Vec<&str> {
+        let allowed = scopes::for_role(self.role);
+        self.scopes
+            .iter()
+            .map(String::as_str)
+            .filter(|scope| allowed.contains(scope))
+            .collect()
+    }
+}
+
+/// Whether `token` looks like an API key rather than a JWT.
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(KEY_PREFIX)
+}
+
+/// Creates a key for `user_id` and returns it together with the key, which
+/// cannot be shown again.
+pub async fn create(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[String],
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(ApiKey, String), ApiError> {
+    let api_key = format!("{KEY_PREFIX}{}", generate_token());
+
+    let record = sqlx::query_as::<_, ApiKey>(
+        r#"
+        INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, prefix, scopes, created_at, expires_at, last_used_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(&api_key[..DISPLAY_PREFIX_LEN])
+    .bind(hash_token(&api_key))
+    .bind(scopes)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23505") => {
+            ApiError::Conflict
+        }
+        _ => {
+            tracing::error!(?e, "failed to create api key");
+            ApiError::Internal
+        }
+    })?;
+
+    Ok((record, api_key))
+}
+
+pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+    sqlx::query_as::<_, ApiKey>(
+        r#"
+        SELECT id, name, prefix, scopes, created_at, expires_at, last_used_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Revokes one of the caller's keys.
+pub async fn delete(pool: &PgPool, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM api_keys WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Resolves a presented key to its owner and records the use. Expired keys
+/// and keys of disabled accounts resolve to `None`.
+pub async fn authenticate(pool: &PgPool, key: &str) -> Result<Option<KeyOwner>, sqlx::Error> {
+    if !key.starts_with(KEY_PREFIX) {
+        return Ok(None);
+    }
+
+    sqlx::query_as::<_, KeyOwner>(
+        r#"
+        UPDATE api_keys k
+        SET last_used_at = NOW()
+        FROM users u
+        WHERE k.key_hash = $1
+          AND u.id = k.user_id
+          AND u.disabled_at IS NULL
+          AND (k.expires_at IS NULL OR k.expires_at > NOW())
+        RETURNING k.user_id, u.role, k.scopes
+        "#,
+    )
+    .bind(hash_token(key))
+    .fetch_optional(pool)
+    .await
+}
+
+/// Resolves a presented key like [`authenticate`] without recording a use,
+/// for callers that only inspect the key, such as introspection.
+pub async fn lookup(pool: &PgPool, key: &str) -> Result<Option<KeyOwner>, sqlx::Error> {
+    if !key.starts_with(KEY_PREFIX) {
+        return Ok(None);
+    }
+
+    sqlx::query_as::<_, KeyOwner>(
+        r#"
+        SELECT k.user_id, u.role, k.scopes
+        FROM api_keys k
+        JOIN users u ON u.id = k.user_id
+        WHERE k.key_hash = $1
+          AND u.disabled_at IS NULL
+          AND (k.expires_at IS NULL OR k.expires_at > NOW())
+        "#,
+    )
+    .bind(hash_token(key))
+    .fetch_optional(pool)
+    .await
+}
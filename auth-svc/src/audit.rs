@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::sessions::ClientInfo;
+
+pub const REGISTER: &str = "REGISTER";
+pub const LOGIN: &str = "LOGIN";
+pub const LOGIN_MFA_PENDING: &str = "LOGIN_MFA_PENDING";
+pub const LOGIN_MFA: &str = "LOGIN_MFA";
+pub const TOKEN_REFRESH: &str = "TOKEN_REFRESH";
+pub const ROLE_CHANGE: &str = "ROLE_CHANGE";
+pub const PASSWORD_CHANGE: &str = "PASSWORD_CHANGE";
+pub const PASSWORD_RESET: &str = "PASSWORD_RESET";
+pub const IMPERSONATE: &str = "IMPERSONATE";
+
+/// An event about to be appended to the audit log.
+pub struct Entry<'a> {
+    event_type: &'static str,
+    success: bool,
+    reason: Option<&'a str>,
+    user_id: Option<Uuid>,
+    username: Option<&'a str>,
+    actor_id: Option<Uuid>,
+    client: Option<&'a ClientInfo>,
+    details: Option<serde_json::Value>,
+}
+
+impl<'a> Entry<'a> {
+    pub fn success(event_type: &'static str) -> Self {
+        Self {
+            event_type,
+            success: true,
+            reason: None,
+            user_id: None,
+            username: None,
+            actor_id: None,
+            client: None,
+            details: None,
+        }
+    }
+
+    pub fn failure(event_type: &'static str, reason: &'a str) -> Self {
+        Self {
+            success: false,
+            reason: Some(reason),
+            ..Self::success(event_type)
+        }
+    }
+
+    pub fn user(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn username(mut self, username: &'a str) -> Self {
+        self.username = Some(username);
+        self
+    }
+
+    /// The admin acting on `user`, when it is not the user themselves.
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn client(mut self, client: &'a ClientInfo) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn reason(mut self, reason: &'a str) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Appends `entry`. A failing audit write is logged but never fails the
+/// request that triggered it.
+pub async fn record(pool: &PgPool, entry: Entry<'_>) {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO audit_log
+            (event_type, success, reason, user_id, username, actor_id, ip, user_agent, details)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+    )
+    .bind(entry.event_type)
+    .bind(entry.success)
+    .bind(entry.reason)
+    .bind(entry.user_id)
+    .bind(entry.username)
+    .bind(entry.actor_id)
+    .bind(entry.client.map(|c| c.ip.as_str()))
+    .bind(entry.client.and_then(|c| c.user_agent.as_deref()))
+    .bind(entry.details)
+    .execute(pool)
+    .await;
+
+    if let Err(err) = result {
+        error!(
+            ?err,
+            event_type = entry.event_type,
+            "failed to write audit log"
+        );
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub event_type: String,
+    pub success: bool,
+    pub reason: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub user_id: Option<Uuid>,
+    pub event_type: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Only return entries older than this id, for paging backwards.
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+    /// `jsonl` exports the result as JSON lines.
+    pub format: Option<String>,
+}
+
+pub const DEFAULT_LIMIT: i64 = 100;
+pub const MAX_LIMIT: i64 = 10_000;
+
+/// Entries matching `query`, newest first.
+pub async fn search(pool: &PgPool, query: &AuditQuery) -> Result<Vec<AuditRecord>, sqlx::Error> {
+    sqlx::query_as::<_, AuditRecord>(
+        r#"
+        SELECT id, event_type, success, reason, user_id, username, actor_id, ip, user_agent,
+               details, created_at
+        FROM audit_log
+        WHERE ($1::UUID IS NULL OR user_id = $1 OR actor_id = $1)
+          AND ($2::TEXT IS NULL OR event_type = $2)
+          AND ($3::TIMESTAMPTZ IS NULL OR created_at >= $3)
+          AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+          AND ($5::BIGINT IS NULL OR id < $5)
+        ORDER BY id DESC
+        LIMIT $6
+        "#,
+    )
+    .bind(query.user_id)
+    .bind(query.event_type.as_deref().map(str::to_uppercase))
+    .bind(query.from)
+    .bind(query.to)
+    .bind(query.before)
+    .bind(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,304 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use anyhow::{Context, Result, bail};
+use sqlx::PgPool;
+
+use crate::{
+    audit,
+    errors::ApiError,
+    messaging::{RoleChangePayload, UserCreatedEvent, UserPayload, UserRoleChangedEvent},
+    passwords::Passwords,
+    revocation,
+    user::{User, UserRole},
+};
+
+const USAGE: &str = "\
+usage: auth-svc admin <command>
+
+commands:
+  create-admin <username>          create an ADMIN account, password read from stdin
+  reset-password <username>        set a new password read from stdin and end all sessions
+  set-role <username> <admin|client>
+  list-users";
+
+/// Runs `auth-svc admin ...` against `DATABASE_URL` and exits. Changes go
+/// through the outbox like their HTTP counterparts, so the running service
+/// publishes the events.
+pub async fn run(args: Vec<String>) -> Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let command = match args.as_slice() {
+        ["create-admin", username] => Command::CreateAdmin(username),
+        ["reset-password", username] => Command::ResetPassword(username),
+        ["set-role", username, role] => Command::SetRole(username, parse_role(role)?),
+        ["list-users"] => Command::ListUsers,
+        _ => bail!("{USAGE}"),
+    };
+
+    let db_url = std::env::var("DATABASE_URL").context("DATABASE_URL must be set.")?;
+    let user_events_queue =
+        std::env::var("USER_EVENTS_QUEUE").unwrap_or_else(|_| "user.events".into());
+
+    let pool = PgPool::connect(&db_url).await?;
+    crate::MIGRATOR.run(&pool).await?;
+
+    match command {
+        Command::CreateAdmin(username) => {
+            let passwords = Passwords::from_env()?;
+            let password = read_password()?;
+            check_policy(&passwords, &password, username)?;
+            create_admin(&pool, &user_events_queue, &passwords, username, &password).await
+        }
+        Command::ResetPassword(username) => {
+            let passwords = Passwords::from_env()?;
+            let password = read_password()?;
+            check_policy(&passwords, &password, username)?;
+            reset_password(&pool, &passwords, username, &password).await
+        }
+        Command::SetRole(username, role) => {
+            set_role(&pool, &user_events_queue, username, role).await
+        }
+        Command::ListUsers => list_users(&pool).await,
+    }
+}
+
+enum Command<'a> {
+    CreateAdmin(&'a str),
+    ResetPassword(&'a str),
+    SetRole(&'a str, UserRole),
+    ListUsers,
+}
+
+fn parse_role(role: &str) -> Result<UserRole> {
+    match role.to_uppercase().as_str() {
+        "ADMIN" => Ok(UserRole::Admin),
+        "CLIENT" => Ok(UserRole::Client),
+        _ => bail!("role must be admin or client"),
+    }
+}
+
+/// Reads the first line of stdin, prompting when it is a terminal. Keeping
+/// the password out of argv keeps it out of `ps` and shell history.
+fn read_password() -> Result<String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+        std::io::stderr().flush()?;
+    }
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        bail!("no password given on stdin");
+    }
+    Ok(password)
+}
+
+fn check_policy(passwords: &Passwords, password: &str, username: &str) -> Result<()> {
+    match passwords.check(password, username) {
+        Ok(()) => Ok(()),
+        Err(ApiError::BadRequest(reason)) => bail!(reason),
+        Err(err) => bail!("{err}"),
+    }
+}
+
+async fn find_user(pool: &PgPool, username: &str) -> Result<User> {
+    sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, role, created_at, disabled_at FROM users WHERE username = $1",
+    )
+    .bind(username)
+    .fetch_optional(pool)
+    .await?
+    .with_context(|| format!("no user named {username}"))
+}
+
+async fn create_admin(
+    pool: &PgPool,
+    user_events_queue: &str,
+    passwords: &Passwords,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    let hash = passwords
+        .hash(password)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to hash password: {e}"))?;
+
+    let mut tx = pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        INSERT INTO users (username, password_hash, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING id, username, password_hash, role, created_at, disabled_at
+        "#,
+    )
+    .bind(username)
+    .bind(&hash)
+    .bind(UserRole::Admin)
+    .fetch_optional(&mut *tx)
+    .await?
+    .with_context(|| format!("user {username} already exists, use set-role to promote it"))?;
+
+    // user-svc provisions the profile from this event, as after /register.
+    outbox::enqueue(
+        &mut tx,
+        user_events_queue,
+        &UserCreatedEvent {
+            event_type: "USER_CREATED",
+            user_id: user.id,
+            payload: UserPayload {
+                id: user.id,
+                username: user.username.clone(),
+                default_unit: "KWH".into(),
+                default_home_type: "HOUSE".into(),
+                default_goal: 300,
+            },
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    audit::record(
+        pool,
+        audit::Entry::success(audit::REGISTER)
+            .user(user.id)
+            .username(&user.username)
+            .details(serde_json::json!({ "source": "cli", "role": user.role })),
+    )
+    .await;
+
+    println!("created admin {} ({})", user.username, user.id);
+    Ok(())
+}
+
+async fn reset_password(
+    pool: &PgPool,
+    passwords: &Passwords,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    let user = find_user(pool, username).await?;
+    let hash = passwords
+        .hash(password)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to hash password: {e}"))?;
+
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+        .bind(user.id)
+        .bind(&hash)
+        .execute(pool)
+        .await?;
+
+    revocation::revoke_all_for_user(pool, user.id).await?;
+
+    audit::record(
+        pool,
+        audit::Entry::success(audit::PASSWORD_RESET)
+            .user(user.id)
+            .username(&user.username)
+            .details(serde_json::json!({ "source": "cli" })),
+    )
+    .await;
+
+    println!("reset password of {} and ended its sessions", user.username);
+    Ok(())
+}
+
+async fn set_role(
+    pool: &PgPool,
+    user_events_queue: &str,
+    username: &str,
+    role: UserRole,
+) -> Result<()> {
+    let user = find_user(pool, username).await?;
+
+    let mut tx = pool.begin().await?;
+
+    // Locked so a concurrent change cannot slip in between the read and the
+    // update and leave USER_ROLE_CHANGED with the wrong previous role.
+    let previous_role =
+        sqlx::query_scalar::<_, UserRole>("SELECT role FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user.id)
+            .fetch_one(&mut *tx)
+            .await?;
+    if previous_role == role {
+        println!("{} is already {role}", user.username);
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE users SET role = $2 WHERE id = $1")
+        .bind(user.id)
+        .bind(role)
+        .execute(&mut *tx)
+        .await?;
+
+    outbox::enqueue(
+        &mut tx,
+        user_events_queue,
+        &UserRoleChangedEvent {
+            event_type: "USER_ROLE_CHANGED",
+            user_id: user.id,
+            payload: RoleChangePayload {
+                id: user.id,
+                previous_role,
+                role,
+                changed_by: None,
+            },
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    // Tokens carry the role, so old ones must not outlive the change.
+    revocation::revoke_all_for_user(pool, user.id).await?;
+
+    audit::record(
+        pool,
+        audit::Entry::success(audit::ROLE_CHANGE)
+            .user(user.id)
+            .username(&user.username)
+            .details(serde_json::json!({ "source": "cli", "from": previous_role, "to": role })),
+    )
+    .await;
+
+    println!(
+        "changed role of {} from {previous_role} to {role}",
+        user.username
+    );
+    Ok(())
+}
+
+async fn list_users(pool: &PgPool) -> Result<()> {
+    let users = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, password_hash, role, created_at, disabled_at
+        FROM users
+        ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    println!(
+        "{:<36}  {:<7}  {:<20}  {:<8}  USERNAME",
+        "ID", "ROLE", "CREATED", "STATUS"
+    );
+    for user in users {
+        println!(
+            "{:<36}  {:<7}  {:<20}  {:<8}  {}",
+            user.id,
+            user.role.to_string(),
+            user.created_at.format("%Y-%m-%d %H:%M:%S"),
+            if user.disabled_at.is_some() {
+                "disabled"
+            } else {
+                "active"
+            },
+            user.username,
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::ApiError,
+    secrets::{generate_token, hash_token},
+};
+
+/// An internal service allowed to use the client-credentials grant.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ServiceClient {
+    pub id: Uuid,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Registers a client and returns it with its secret. Only the secret's
+/// SHA-256 digest is stored, so it cannot be shown again.
+pub async fn create(
+    pool: &PgPool,
+    client_id: &str,
+    scopes: &[String],
+    admin_id: Uuid,
+) -> Result<(ServiceClient, String), ApiError> {
+    let secret = generate_token();
+
+    let client = sqlx::query_as::<_, ServiceClient>(
+        r#"
+        INSERT INTO service_clients (client_id, secret_hash, scopes, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, client_id, scopes, created_by, created_at
+        "#,
+    )
+    .bind(client_id)
+    .bind(hash_token(&secret))
+    .bind(scopes)
+    .bind(admin_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23505") => {
+            ApiError::Conflict
+        }
+        _ => {
+            tracing::error!(?e, "failed to create service client");
+            ApiError::Internal
+        }
+    })?;
+
+    Ok((client, secret))
+}
+
+pub async fn list(pool: &PgPool) -> Result<Vec<ServiceClient>, sqlx::Error> {
+    sqlx::query_as::<_, ServiceClient>(
+        r#"
+        SELECT id, client_id, scopes, created_by, created_at
+        FROM service_clients
+        ORDER BY client_id
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Removes a client. Tokens it already holds stop passing `/verify`.
+pub async fn delete(pool: &PgPool, client_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM service_clients WHERE client_id = $1")
+        .bind(client_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn authenticate(
+    pool: &PgPool,
+    client_id: &str,
+    secret: &str,
+) -> Result<ServiceClient, ApiError> {
+    sqlx::query_as::<_, ServiceClient>(
+        r#"
+        SELECT id, client_id, scopes, created_by, created_at
+        FROM service_clients
+        WHERE client_id = $1 AND secret_hash = $2
+        "#,
+    )
+    .bind(client_id)
+    .bind(hash_token(secret))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "db error on client authentication");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::BadCredentials)
+}
@@ -1,20 +1,136 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, Result, bail};
+
+use crate::user::UserRole;
+
+#[derive(Clone)]
 pub struct JwtConfig {
-    pub secret: String,
     pub issuer: String,
     pub audience: String,
     pub ttl_seconds: i64,
+    pub refresh_ttl_seconds: i64,
+    pub service_ttl_seconds: i64,
+    pub impersonation_ttl_seconds: i64,
 }
 
 impl JwtConfig {
+    /// Reads and validates the token settings, naming the offending variable
+    /// on error.
+    pub fn from_env() -> Result<Self> {
+        fn required(name: &str) -> Result<String> {
+            let value = std::env::var(name).with_context(|| format!("{name} is required."))?;
+            if value.trim().is_empty() {
+                bail!("{name} must not be empty.");
+            }
+            Ok(value)
+        }
+
+        fn ttl(name: &str, default: i64) -> Result<i64> {
+            let value = std::env::var(name).unwrap_or_default();
+            if value.trim().is_empty() {
+                return Ok(default);
+            }
+            match value.trim().parse::<i64>() {
+                Ok(seconds) if seconds > 0 => Ok(seconds),
+                _ => bail!("{name} must be a positive number of seconds, got {value:?}."),
+            }
+        }
+
+        Ok(Self {
+            issuer: required("JWT_ISSUER")?,
+            audience: required("JWT_AUDIENCE")?,
+            ttl_seconds: ttl("ACCESS_TOKEN_TTL_SECONDS", 3600)?,
+            refresh_ttl_seconds: ttl("REFRESH_TOKEN_TTL_SECONDS", 2_592_000)?,
+            service_ttl_seconds: ttl("SERVICE_TOKEN_TTL_SECONDS", 900)?,
+            impersonation_ttl_seconds: ttl("IMPERSONATION_TOKEN_TTL_SECONDS", 900)?,
+        })
+    }
+}
+
+pub struct ThrottleConfig {
+    pub max_failures_per_user: i32,
+    pub max_failures_per_ip: i32,
+    pub failure_window_seconds: i64,
+    pub base_lockout_seconds: i64,
+    pub max_lockout_seconds: i64,
+    /// Proxies allowed to report the client address in `X-Forwarded-For`.
+    /// Empty means the header's last hop, written by Traefik, is used.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl ThrottleConfig {
+    pub fn from_env() -> Result<Self> {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<T>().ok())
+                .unwrap_or(default)
+        }
+
+        let trusted_proxies = std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(|proxy| {
+                proxy.parse::<IpAddr>().with_context(|| {
+                    format!("TRUSTED_PROXIES contains an invalid address {proxy:?}.")
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            max_failures_per_user: var("LOGIN_MAX_FAILURES_PER_USER", 5),
+            max_failures_per_ip: var("LOGIN_MAX_FAILURES_PER_IP", 20),
+            failure_window_seconds: var("LOGIN_FAILURE_WINDOW_SECONDS", 900),
+            base_lockout_seconds: var("LOGIN_LOCKOUT_BASE_SECONDS", 30),
+            max_lockout_seconds: var("LOGIN_LOCKOUT_MAX_SECONDS", 3600),
+            trusted_proxies,
+        })
+    }
+}
+
+pub struct MfaConfig {
+    pub issuer: String,
+    pub required_roles: Vec<UserRole>,
+    pub challenge_ttl_seconds: i64,
+    /// How long a pending enrollment secret is handed out again before a
+    /// new one replaces it.
+    pub enrollment_ttl_seconds: i64,
+    /// Wrong codes after which a login challenge is burnt.
+    pub max_attempts: i32,
+}
+
+impl MfaConfig {
     pub fn from_env() -> Self {
-        Self {
-            secret: std::env::var("JWT_SECRET").expect("JWT_SECRET is required."),
-            issuer: std::env::var("JWT_ISSUER").expect("JWT_ISSUER is required."),
-            audience: std::env::var("JWT_AUDIENCE").expect("JWT_AUDIENCE is required."),
-            ttl_seconds: std::env::var("ACCESS_TOKEN_TTL_SECONDS")
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
                 .ok()
-                .and_then(|v| v.trim().parse::<i64>().ok())
-                .unwrap_or(3600),
+                .and_then(|v| v.trim().parse::<T>().ok())
+                .unwrap_or(default)
         }
+
+        let required_roles = std::env::var("MFA_REQUIRED_ROLES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|role| match role.trim().to_uppercase().as_str() {
+                "ADMIN" => Some(UserRole::Admin),
+                "CLIENT" => Some(UserRole::Client),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            issuer: std::env::var("TOTP_ISSUER").unwrap_or_else(|_| "Watt".into()),
+            required_roles,
+            challenge_ttl_seconds: var("MFA_CHALLENGE_TTL_SECONDS", 300),
+            enrollment_ttl_seconds: var("MFA_ENROLLMENT_TTL_SECONDS", 900),
+            max_attempts: var("MFA_MAX_ATTEMPTS", 5),
+        }
+    }
+
+    pub fn required_for(&self, role: UserRole) -> bool {
+        self.required_roles.contains(&role)
     }
 }
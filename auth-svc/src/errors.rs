@@ -1,12 +1,18 @@
 use std::fmt::{Display, Formatter};
 
-use axum::response::{IntoResponse, Response};
-use reqwest::StatusCode;
+use axum::{
+    http::{StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
 
 #[derive(Debug)]
 pub enum ApiError {
     BadCredentials,
+    BadRequest(String),
+    Forbidden,
+    NotFound,
     Conflict,
+    TooManyRequests { retry_after: u64 },
     Internal,
 }
 
@@ -14,7 +20,11 @@ impl Display for ApiError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ApiError::BadCredentials => f.write_str("bad credentials"),
+            ApiError::BadRequest(err) => write!(f, "bad request: {}", err),
+            ApiError::Forbidden => f.write_str("forbidden"),
+            ApiError::NotFound => f.write_str("not found"),
             ApiError::Conflict => f.write_str("conflict"),
+            ApiError::TooManyRequests { .. } => f.write_str("too many failed attempts"),
             ApiError::Internal => f.write_str("internal server error"),
         }
     }
@@ -24,7 +34,18 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let status = match self {
             ApiError::BadCredentials => StatusCode::UNAUTHORIZED,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
             ApiError::Conflict => StatusCode::CONFLICT,
+            ApiError::TooManyRequests { retry_after } => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after.to_string())],
+                    self.to_string(),
+                )
+                    .into_response();
+            }
             ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
         };
         (status, self.to_string()).into_response()
@@ -1,206 +1,1708 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
-use argon2::{
-    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
-    password_hash::{SaltString, rand_core::OsRng},
-};
 use axum::{
     Json,
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    extract::{ConnectInfo, Form, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use reqwest::Client;
-use serde::Serialize;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use jsonwebtoken::jwk::JwkSet;
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
-    AppState,
+    AppState, api_keys,
+    audit::{self, AuditQuery},
+    clients::{self, ServiceClient},
     config::JwtConfig,
     errors::ApiError,
-    jwt::{sign, verify},
-    messaging::{UserCreatedEvent, UserPayload},
-    user::{AuthResponse, LoginRequest, RegisterRequest, User},
+    jwt::{AuthUser, Claims, sign, sign_impersonation, sign_service, verify},
+    messaging::{
+        DeletionPayload, RoleChangePayload, UserCreatedEvent, UserDeletedEvent, UserPayload,
+        UserRoleChangedEvent,
+    },
+    mfa::{self, TotpEnrollment},
+    password_reset, refresh, revocation, scopes,
+    sessions::{self, ClientInfo, Session},
+    throttle::{self, Lockout},
+    user::{
+        AuthResponse, ChangePasswordRequest, ChangeRoleRequest, CreateApiKeyRequest,
+        CreateClientRequest, CreatedApiKeyResponse, CreatedClientResponse, DeleteAccountRequest,
+        ImpersonationResponse, IntrospectRequest, IntrospectResponse, LoginRequest, LoginResponse,
+        LogoutRequest, MfaChallengeResponse, MfaLoginRequest, MfaLoginResponse,
+        PasswordResetResponse, RecoveryCodesResponse, RefreshRequest, RegisterRequest,
+        ResetPasswordRequest, ServiceTokenResponse, TokenRequest, TotpCodeRequest,
+        TotpStatusResponse, User, UserResponse, UserRole,
+    },
 };
 
 pub async fn health_check() -> impl IntoResponse {
     StatusCode::OK
 }
 
-#[derive(Serialize)]
-struct CreateUserPayload {
-    unit_energy: String,
-    home_type: String,
-    goal_kwh_month: i64,
-}
-
 pub async fn register(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<RegisterRequest>,
-) -> Result<Json<AuthResponse>, ApiError> {
-    let hash = hash_password(&payload.password).map_err(|_| ApiError::Internal)?;
+) -> Result<Json<LoginResponse>, ApiError> {
+    let client = ClientInfo::from_request(&headers, peer, &state.throttle.trusted_proxies);
+    state
+        .passwords
+        .check(&payload.password, &payload.username)?;
+    let hash = state
+        .passwords
+        .hash(&payload.password)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
 
     let user = sqlx::query_as::<_, User>(
         r#"
     INSERT INTO users (username, password_hash)
     VALUES ($1, $2)
-    RETURNING id, username, password_hash, role, created_at
+    RETURNING id, username, password_hash, role, created_at, disabled_at
     "#,
     )
     .bind(&payload.username)
     .bind(&hash)
-    .fetch_one(&state.db_pool)
+    .fetch_one(&mut *tx)
+    .await;
+
+    let user = match user {
+        Ok(user) => user,
+        Err(sqlx::Error::Database(ref db)) if db.code().as_deref() == Some("23505") => {
+            audit::record(
+                &state.db_pool,
+                audit::Entry::failure(audit::REGISTER, "username_taken")
+                    .username(&payload.username)
+                    .client(&client),
+            )
+            .await;
+            return Err(ApiError::Conflict);
+        }
+        Err(_) => return Err(ApiError::Internal),
+    };
+
+    // user-svc provisions the profile from this event.
+    outbox::enqueue(
+        &mut tx,
+        &state.user_events_queue,
+        &UserCreatedEvent {
+            event_type: "USER_CREATED",
+            user_id: user.id,
+            payload: UserPayload {
+                id: user.id,
+                username: user.username.clone(),
+                default_unit: "KWH".into(),
+                default_home_type: "HOUSE".into(),
+                default_goal: 300,
+            },
+        },
+    )
     .await
     .map_err(|e| {
-        if let sqlx::Error::Database(db) = &e {
-            if db.code().as_deref() == Some("23505") {
-                return ApiError::Conflict;
-            }
-        }
+        tracing::error!(?e, "failed to enqueue USER_CREATED event");
         ApiError::Internal
     })?;
 
-    let cfg = JwtConfig::from_env();
-    let token = sign(user.id, user.role, &cfg).map_err(|e| {
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::REGISTER)
+            .user(user.id)
+            .username(&user.username)
+            .client(&client),
+    )
+    .await;
+
+    // Roles that require MFA get no tokens until the first code activates
+    // the enrollment, the same as on their first login.
+    if state.mfa.required_for(user.role) {
+        let challenge = mfa_challenge(&state, &user, false).await?;
+        return Ok(Json(LoginResponse::MfaRequired(challenge)));
+    }
+
+    let jwt = state.jwt();
+    let cfg = &jwt.config;
+    let (session_id, refresh_token) = start_session(&state, user.id, &client, cfg).await?;
+
+    let token = sign(user.id, user.role, session_id, &jwt).map_err(|e| {
         tracing::error!(?e, "jwt sign failed");
         ApiError::Internal
     })?;
 
-    let create_body = CreateUserPayload {
-        unit_energy: "KWH".into(),
-        home_type: "HOUSE".into(),
-        goal_kwh_month: 300,
+    Ok(Json(LoginResponse::Tokens(auth_response(
+        token,
+        refresh_token,
+        cfg,
+    ))))
+}
+
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let client = ClientInfo::from_request(&headers, peer, &state.throttle.trusted_proxies);
+    let ip = client.ip.clone();
+
+    if let Some(retry_after) = throttle::locked_for(&state.db_pool, &payload.username, &ip)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "db error on login throttle");
+            ApiError::Internal
+        })?
+    {
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::LOGIN, "locked_out")
+                .username(&payload.username)
+                .client(&client),
+        )
+        .await;
+        return Err(ApiError::TooManyRequests { retry_after });
+    }
+
+    let user = sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, role, created_at, disabled_at FROM users WHERE username=$1",
+    )
+    .bind(&payload.username)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "db error on login");
+        ApiError::Internal
+    })?;
+
+    let verified = match &user {
+        Some(user) => state
+            .passwords
+            .verify(&payload.password, &user.password_hash)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!(?e, "bad password hash");
+                false
+            }),
+        None => false,
+    };
+    let Some(user) = user.filter(|_| verified) else {
+        if let Err(err) =
+            throttle::record_failure(&state.db_pool, &state.throttle, &payload.username, &ip).await
+        {
+            error!(?err, "failed to record login failure");
+        }
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::LOGIN, "bad_credentials")
+                .username(&payload.username)
+                .client(&client),
+        )
+        .await;
+        return Err(ApiError::BadCredentials);
     };
 
-    let traefik = std::env::var("TRAEFIK_URL").unwrap_or_else(|_| "http://traefik:80".into());
-    let host = std::env::var("TRAEFIK_HOST").unwrap_or_else(|_| "localhost".into());
-    let url = format!("{}/user/create", traefik);
+    if user.disabled_at.is_some() {
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::LOGIN, "account_disabled")
+                .user(user.id)
+                .username(&user.username)
+                .client(&client),
+        )
+        .await;
+        return Err(ApiError::Forbidden);
+    }
+
+    if state.passwords.needs_rehash(&user.password_hash) {
+        rehash_password(&state, &user, &payload.password).await;
+    }
+
+    let mfa_enabled = mfa::is_enabled(&state.db_pool, user.id)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    if mfa_enabled || state.mfa.required_for(user.role) {
+        let challenge = mfa_challenge(&state, &user, mfa_enabled).await?;
+
+        audit::record(
+            &state.db_pool,
+            audit::Entry::success(audit::LOGIN_MFA_PENDING)
+                .user(user.id)
+                .username(&user.username)
+                .client(&client),
+        )
+        .await;
+
+        return Ok(Json(LoginResponse::MfaRequired(challenge)));
+    }
+
+    if let Err(err) = throttle::record_success(&state.db_pool, &user.username).await {
+        error!(?err, "failed to reset login failures");
+    }
+
+    let jwt = state.jwt();
+    let cfg = &jwt.config;
+    let (session_id, refresh_token) = start_session(&state, user.id, &client, cfg).await?;
+
+    let token = sign(user.id, user.role, session_id, &jwt).map_err(|e| {
+        tracing::error!(?e, "jwt sign failed");
+        ApiError::Internal
+    })?;
+
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::LOGIN)
+            .user(user.id)
+            .username(&user.username)
+            .client(&client),
+    )
+    .await;
+
+    Ok(Json(LoginResponse::Tokens(auth_response(
+        token,
+        refresh_token,
+        cfg,
+    ))))
+}
+
+/// Opens the second login step for `user`. Roles that require MFA enroll on
+/// their first login: until TOTP is enabled, the challenge carries the
+/// pending secret and the first valid code activates it.
+async fn mfa_challenge(
+    state: &AppState,
+    user: &User,
+    mfa_enabled: bool,
+) -> Result<MfaChallengeResponse, ApiError> {
+    let cfg = &state.mfa;
+    let enrollment = if mfa_enabled {
+        None
+    } else {
+        Some(mfa::begin_enrollment(&state.db_pool, user.id, &user.username, cfg).await?)
+    };
 
-    let resp = Client::new()
-        .post(&url)
-        .header("Host", host)
-        .bearer_auth(&token)
-        .json(&create_body)
-        .send()
+    let challenge_token = mfa::create_challenge(&state.db_pool, user.id, cfg)
         .await
         .map_err(|e| {
-            tracing::error!(?e, "user-svc call failed");
+            tracing::error!(?e, "failed to create mfa challenge");
             ApiError::Internal
         })?;
 
-    if let Err(e) = resp.error_for_status_ref() {
-        tracing::error!(
-            ?e,
-            "user-svc /create returned error; rolling back auth user"
-        );
-        let _ = sqlx::query("DELETE FROM users WHERE id = $1")
-            .bind(user.id)
-            .execute(&state.db_pool)
-            .await;
-        return Err(ApiError::Internal);
-    }
+    Ok(MfaChallengeResponse {
+        mfa_required: true,
+        challenge_token,
+        expires_in: cfg.challenge_ttl_seconds,
+        enrollment,
+    })
+}
 
-    if let Err(err) = state
-        .publisher
-        .publish_user_created(&UserCreatedEvent {
-            event_type: "USER_CREATED",
-            user_id: user.id,
-            payload: UserPayload {
-                id: user.id,
-                username: user.username.clone(),
-                default_unit: create_body.unit_energy.clone(),
-                default_home_type: create_body.home_type.clone(),
-                default_goal: create_body.goal_kwh_month,
-            },
-        })
+pub async fn complete_mfa_login(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<MfaLoginRequest>,
+) -> Result<Json<MfaLoginResponse>, ApiError> {
+    let client = ClientInfo::from_request(&headers, peer, &state.throttle.trusted_proxies);
+    let ip = client.ip.clone();
+
+    let username = mfa::challenge_username(&state.db_pool, &payload.challenge_token)
+        .await
+        .map_err(|_| ApiError::Internal)?
+        .ok_or(ApiError::BadCredentials)?;
+
+    if let Some(retry_after) = throttle::locked_for(&state.db_pool, &username, &ip)
         .await
+        .map_err(|_| ApiError::Internal)?
     {
-        error!(?err, "failed to publish USER_CREATED event");
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::LOGIN_MFA, "locked_out")
+                .username(&username)
+                .client(&client),
+        )
+        .await;
+        return Err(ApiError::TooManyRequests { retry_after });
     }
 
-    Ok(Json(AuthResponse {
-        access_token: token,
-        token_type: "Bearer".into(),
-        expires_in: cfg.ttl_seconds,
+    let completed = match mfa::complete_challenge(
+        &state.db_pool,
+        &payload.challenge_token,
+        &payload.code,
+        &state.mfa,
+    )
+    .await
+    {
+        Ok(completed) => completed,
+        Err(ApiError::BadCredentials) => {
+            if let Err(err) =
+                throttle::record_failure(&state.db_pool, &state.throttle, &username, &ip).await
+            {
+                error!(?err, "failed to record mfa failure");
+            }
+            audit::record(
+                &state.db_pool,
+                audit::Entry::failure(audit::LOGIN_MFA, "invalid_code")
+                    .username(&username)
+                    .client(&client),
+            )
+            .await;
+            return Err(ApiError::BadCredentials);
+        }
+        Err(err) => return Err(err),
+    };
+
+    if let Err(err) = throttle::record_success(&state.db_pool, &username).await {
+        error!(?err, "failed to reset login failures");
+    }
+
+    let user = sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, role, created_at, disabled_at FROM users WHERE id = $1",
+    )
+    .bind(completed.user_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| ApiError::Internal)?
+    .filter(|user| user.disabled_at.is_none())
+    .ok_or(ApiError::Forbidden)?;
+
+    let jwt = state.jwt();
+    let cfg = &jwt.config;
+    let (session_id, refresh_token) = start_session(&state, user.id, &client, cfg).await?;
+
+    let token = sign(user.id, user.role, session_id, &jwt).map_err(|e| {
+        tracing::error!(?e, "jwt sign failed");
+        ApiError::Internal
+    })?;
+
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::LOGIN)
+            .reason("mfa")
+            .user(user.id)
+            .username(&user.username)
+            .client(&client),
+    )
+    .await;
+
+    Ok(Json(MfaLoginResponse {
+        tokens: auth_response(token, refresh_token, cfg),
+        recovery_codes: completed.recovery_codes,
     }))
 }
 
-pub async fn login(
+pub async fn totp_status(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<LoginRequest>,
+    user: AuthUser,
+) -> Result<Json<TotpStatusResponse>, ApiError> {
+    let enabled = mfa::is_enabled(&state.db_pool, user.user_id)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    Ok(Json(TotpStatusResponse {
+        enabled,
+        required: state.mfa.required_for(user.role),
+    }))
+}
+
+pub async fn enroll_totp(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<TotpEnrollment>, ApiError> {
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(user.user_id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|_| ApiError::Internal)?
+        .ok_or(ApiError::NotFound)?;
+
+    let enrollment =
+        mfa::begin_enrollment(&state.db_pool, user.user_id, &username, &state.mfa).await?;
+
+    Ok(Json(enrollment))
+}
+
+pub async fn activate_totp(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let recovery_codes =
+        mfa::activate(&state.db_pool, user.user_id, &payload.code, &state.mfa).await?;
+
+    tracing::info!(user_id = %user.user_id, "totp enabled");
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn disable_totp(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<StatusCode, ApiError> {
+    if state.mfa.required_for(user.role) {
+        return Err(ApiError::Forbidden);
+    }
+
+    if !mfa::verify(&state.db_pool, user.user_id, &payload.code).await? {
+        return Err(ApiError::BadCredentials);
+    }
+
+    mfa::disable(&state.db_pool, user.user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to disable totp");
+            ApiError::Internal
+        })?;
+
+    tracing::info!(user_id = %user.user_id, "totp disabled");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn refresh(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshRequest>,
 ) -> Result<Json<AuthResponse>, ApiError> {
-    let user = sqlx::query_as::<_, User>(
-        "SELECT id, username, password_hash, role, created_at FROM users WHERE username=$1",
+    let jwt = state.jwt();
+    let cfg = &jwt.config;
+    let client = ClientInfo::from_request(&headers, peer, &state.throttle.trusted_proxies);
+
+    let rotated = match refresh::rotate(
+        &state.db_pool,
+        &payload.refresh_token,
+        cfg.refresh_ttl_seconds,
     )
-    .bind(&payload.username)
+    .await
+    {
+        Ok(rotated) => rotated,
+        Err(ApiError::BadCredentials) => {
+            audit::record(
+                &state.db_pool,
+                audit::Entry::failure(audit::TOKEN_REFRESH, "invalid_refresh_token")
+                    .client(&client),
+            )
+            .await;
+            return Err(ApiError::BadCredentials);
+        }
+        Err(err) => return Err(err),
+    };
+
+    // A session opened before the role required MFA must not be kept alive
+    // past it; the user has to log in again and enroll.
+    if state.mfa.required_for(rotated.role)
+        && !mfa::is_enabled(&state.db_pool, rotated.user_id)
+            .await
+            .map_err(|_| ApiError::Internal)?
+    {
+        if let Err(err) = sessions::revoke(&state.db_pool, rotated.user_id, rotated.family_id).await
+        {
+            error!(?err, "failed to revoke session without mfa");
+        }
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::TOKEN_REFRESH, "mfa_required")
+                .user(rotated.user_id)
+                .client(&client),
+        )
+        .await;
+        return Err(ApiError::Forbidden);
+    }
+
+    let token = sign(rotated.user_id, rotated.role, rotated.family_id, &jwt).map_err(|e| {
+        tracing::error!(?e, "jwt sign failed");
+        ApiError::Internal
+    })?;
+
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::TOKEN_REFRESH)
+            .user(rotated.user_id)
+            .client(&client)
+            .details(serde_json::json!({ "session_id": rotated.family_id })),
+    )
+    .await;
+
+    Ok(Json(auth_response(token, rotated.refresh_token, cfg)))
+}
+
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    payload: Option<Json<LogoutRequest>>,
+) -> Result<StatusCode, ApiError> {
+    revocation::revoke_token(&state.db_pool, &user.claims, user.user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to revoke access token");
+            ApiError::Internal
+        })?;
+
+    if let Some(session_id) = user.claims.session_id() {
+        sessions::revoke(&state.db_pool, user.user_id, session_id)
+            .await
+            .map_err(|e| {
+                tracing::error!(?e, "failed to revoke session");
+                ApiError::Internal
+            })?;
+    } else if let Some(refresh_token) = payload.and_then(|Json(body)| body.refresh_token) {
+        refresh::revoke_family_of(&state.db_pool, user.user_id, &refresh_token)
+            .await
+            .map_err(|e| {
+                tracing::error!(?e, "failed to revoke refresh token");
+                ApiError::Internal
+            })?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn revoke_user_sessions(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    revocation::revoke_all_for_user(&state.db_pool, target)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23503") => {
+                ApiError::NotFound
+            }
+            _ => {
+                tracing::error!(?e, "failed to revoke user sessions");
+                ApiError::Internal
+            }
+        })?;
+
+    tracing::info!(admin_id = %user.user_id, user_id = %target, "revoked all sessions");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (username, stored) = sqlx::query_as::<_, (String, String)>(
+        "SELECT username, password_hash FROM users WHERE id = $1",
+    )
+    .bind(user.user_id)
     .fetch_optional(&state.db_pool)
     .await
     .map_err(|e| {
-        tracing::error!(?e, "db error on login");
+        tracing::error!(?e, "db error on password change");
         ApiError::Internal
     })?
     .ok_or(ApiError::BadCredentials)?;
 
-    if !verify_password(&payload.password, &user.password_hash).map_err(|e| {
-        tracing::warn!(?e, "bad password hash");
-        ApiError::BadCredentials
-    })? {
+    if !state
+        .passwords
+        .verify(&payload.old_password, &stored)
+        .await
+        .map_err(|e| {
+            tracing::warn!(?e, "bad password hash");
+            ApiError::BadCredentials
+        })?
+    {
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::PASSWORD_CHANGE, "wrong_password").user(user.user_id),
+        )
+        .await;
         return Err(ApiError::BadCredentials);
     }
 
-    let cfg = JwtConfig::from_env();
-    let token = sign(user.id, user.role, &cfg).map_err(|e| {
-        tracing::error!(?e, "jwt sign failed");
-        ApiError::Internal
-    })?;
+    if let Err(err) = state.passwords.check(&payload.new_password, &username) {
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::PASSWORD_CHANGE, "weak_password").user(user.user_id),
+        )
+        .await;
+        return Err(err);
+    }
 
-    Ok(Json(AuthResponse {
-        access_token: token,
-        token_type: "Bearer".into(),
-        expires_in: cfg.ttl_seconds,
-    }))
-}
+    let hash = state
+        .passwords
+        .hash(&payload.new_password)
+        .await
+        .map_err(|_| ApiError::Internal)?;
 
-pub async fn verify_token(headers: HeaderMap) -> Result<impl IntoResponse, StatusCode> {
-    let token = extract_bearer(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+        .bind(user.user_id)
+        .bind(&hash)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to store new password");
+            ApiError::Internal
+        })?;
 
-    let cfg = JwtConfig::from_env();
+    revoke_sessions_after_password_change(&state, user.user_id).await;
 
-    let claims = verify(token, &cfg).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::PASSWORD_CHANGE).user(user.user_id),
+    )
+    .await;
 
-    let mut out = HeaderMap::new();
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
-    out.insert("X-User-Id", user_id.to_string().parse().unwrap());
+pub async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<DeleteAccountRequest>,
+) -> Result<StatusCode, ApiError> {
+    let stored = sqlx::query_scalar::<_, String>("SELECT password_hash FROM users WHERE id = $1")
+        .bind(user.user_id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "db error on account deletion");
+            ApiError::Internal
+        })?
+        .ok_or(ApiError::BadCredentials)?;
 
-    out.insert("X-User-Role", claims.role.to_string().parse().unwrap());
-    Ok((StatusCode::OK, out))
+    if !state
+        .passwords
+        .verify(&payload.password, &stored)
+        .await
+        .map_err(|e| {
+            tracing::warn!(?e, "bad password hash");
+            ApiError::BadCredentials
+        })?
+    {
+        return Err(ApiError::BadCredentials);
+    }
+
+    delete_user_account(&state, user.user_id, user.user_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-fn extract_bearer(headers: &HeaderMap) -> Option<&str> {
-    let auth = headers
-        .get(axum::http::header::AUTHORIZATION)?
-        .to_str()
-        .ok()?;
-    let (scheme, token) = auth.split_once(' ')?;
-    if scheme.eq_ignore_ascii_case("bearer") {
-        Some(token)
-    } else {
-        None
+pub async fn delete_user(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+    if target == user.user_id {
+        return Err(ApiError::BadRequest(
+            "admins cannot delete their own account here".to_string(),
+        ));
     }
+
+    delete_user_account(&state, target, user.user_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-fn hash_password(pwd: &str) -> Result<String, argon2::password_hash::Error> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon = Argon2::default();
-    let hash = argon.hash_password(pwd.as_bytes(), &salt)?;
-    Ok(hash.to_string())
+pub async fn issue_password_reset(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+) -> Result<Json<PasswordResetResponse>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let issued = password_reset::issue(
+        &state.db_pool,
+        target,
+        user.user_id,
+        state.password_reset_ttl_seconds,
+    )
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23503") => {
+            ApiError::NotFound
+        }
+        _ => {
+            tracing::error!(?e, "failed to issue password reset");
+            ApiError::Internal
+        }
+    })?;
+
+    tracing::info!(admin_id = %user.user_id, user_id = %target, "issued password reset");
+
+    Ok(Json(PasswordResetResponse {
+        reset_token: issued.token,
+        expires_at: issued.expires_at,
+    }))
 }
 
-fn verify_password(password: &str, stored: &str) -> Result<bool, argon2::password_hash::Error> {
-    let parsed = PasswordHash::new(stored)?;
-    Ok(Argon2::default()
-        .verify_password(password.as_bytes(), &parsed)
-        .is_ok())
+pub async fn impersonate_user(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+) -> Result<Json<ImpersonationResponse>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+    if target == user.user_id {
+        return Err(ApiError::BadRequest(
+            "admins cannot impersonate themselves".to_string(),
+        ));
+    }
+
+    let client = ClientInfo::from_request(&headers, peer, &state.throttle.trusted_proxies);
+
+    let target_user = sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, role, created_at, disabled_at FROM users WHERE id = $1",
+    )
+    .bind(target)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "db error on impersonation");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::NotFound)?;
+
+    let refusal = if target_user.role != UserRole::Client {
+        Some("target_not_client")
+    } else if target_user.disabled_at.is_some() {
+        Some("account_disabled")
+    } else {
+        None
+    };
+    if let Some(reason) = refusal {
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::IMPERSONATE, reason)
+                .user(target_user.id)
+                .username(&target_user.username)
+                .actor(user.user_id)
+                .client(&client),
+        )
+        .await;
+        return Err(ApiError::BadRequest(
+            "only active client accounts can be impersonated".to_string(),
+        ));
+    }
+
+    let jwt = state.jwt();
+    let access_token = sign_impersonation(target_user.id, target_user.role, user.user_id, &jwt)
+        .map_err(|e| {
+            tracing::error!(?e, "jwt sign failed");
+            ApiError::Internal
+        })?;
+    let expires_in = jwt.config.impersonation_ttl_seconds;
+
+    tracing::info!(admin_id = %user.user_id, user_id = %target, "issued impersonation token");
+
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::IMPERSONATE)
+            .user(target_user.id)
+            .username(&target_user.username)
+            .actor(user.user_id)
+            .client(&client)
+            .details(serde_json::json!({ "expires_in": expires_in })),
+    )
+    .await;
+
+    Ok(Json(ImpersonationResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in,
+        user_id: target_user.id,
+        impersonated_by: user.user_id,
+    }))
+}
+
+pub async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, ApiError> {
+    let username = password_reset::pending_username(&state.db_pool, &payload.reset_token)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "db error on password reset");
+            ApiError::Internal
+        })?;
+    let Some(username) = username else {
+        audit::record(
+            &state.db_pool,
+            audit::Entry::failure(audit::PASSWORD_RESET, "invalid_reset_token"),
+        )
+        .await;
+        return Err(ApiError::BadCredentials);
+    };
+    state.passwords.check(&payload.new_password, &username)?;
+
+    let hash = state
+        .passwords
+        .hash(&payload.new_password)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    let user_id = match password_reset::redeem(&state.db_pool, &payload.reset_token, &hash).await {
+        Ok(user_id) => user_id,
+        Err(ApiError::BadCredentials) => {
+            audit::record(
+                &state.db_pool,
+                audit::Entry::failure(audit::PASSWORD_RESET, "invalid_reset_token"),
+            )
+            .await;
+            return Err(ApiError::BadCredentials);
+        }
+        Err(err) => return Err(err),
+    };
+
+    revoke_sessions_after_password_change(&state, user_id).await;
+
+    audit::record(
+        &state.db_pool,
+        audit::Entry::success(audit::PASSWORD_RESET).user(user_id),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_users(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<UserResponse>>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let users = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, password_hash, role, created_at, disabled_at
+        FROM users
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "failed to list users");
+        ApiError::Internal
+    })?;
+
+    Ok(Json(users.into_iter().map(UserResponse::from).collect()))
+}
+
+pub async fn change_role(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+    Json(payload): Json<ChangeRoleRequest>,
+) -> Result<Json<UserResponse>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+    if target == user.user_id {
+        return Err(ApiError::BadRequest(
+            "admins cannot change their own role".to_string(),
+        ));
+    }
+    if payload.role == UserRole::Service {
+        return Err(ApiError::BadRequest(
+            "the service role is reserved for service clients".to_string(),
+        ));
+    }
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    // Locked so a concurrent change cannot slip in between the read and the
+    // update and leave USER_ROLE_CHANGED with the wrong previous role.
+    let previous_role =
+        sqlx::query_scalar::<_, UserRole>("SELECT role FROM users WHERE id = $1 FOR UPDATE")
+            .bind(target)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| ApiError::Internal)?
+            .ok_or(ApiError::NotFound)?;
+
+    let updated = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users
+        SET role = $2
+        WHERE id = $1
+        RETURNING id, username, password_hash, role, created_at, disabled_at
+        "#,
+    )
+    .bind(target)
+    .bind(payload.role)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "failed to change role");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::NotFound)?;
+
+    if previous_role != updated.role {
+        outbox::enqueue(
+            &mut tx,
+            &state.user_events_queue,
+            &UserRoleChangedEvent {
+                event_type: "USER_ROLE_CHANGED",
+                user_id: target,
+                payload: RoleChangePayload {
+                    id: target,
+                    previous_role,
+                    role: updated.role,
+                    changed_by: Some(user.user_id),
+                },
+            },
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to enqueue USER_ROLE_CHANGED event");
+            ApiError::Internal
+        })?;
+    }
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    if previous_role != updated.role {
+        // Tokens carry the role, so old ones must not outlive the change.
+        if let Err(err) = revocation::revoke_all_for_user(&state.db_pool, target).await {
+            error!(?err, user_id = %target, "failed to revoke sessions after role change");
+        }
+
+        tracing::info!(
+            admin_id = %user.user_id,
+            user_id = %target,
+            from = %previous_role,
+            to = %updated.role,
+            "changed user role"
+        );
+
+        audit::record(
+            &state.db_pool,
+            audit::Entry::success(audit::ROLE_CHANGE)
+                .user(target)
+                .username(&updated.username)
+                .actor(user.user_id)
+                .details(serde_json::json!({ "from": previous_role, "to": updated.role })),
+        )
+        .await;
+    }
+
+    Ok(Json(updated.into()))
+}
+
+pub async fn disable_user(
+    state: State<Arc<AppState>>,
+    user: AuthUser,
+    target: Path<Uuid>,
+) -> Result<Json<UserResponse>, ApiError> {
+    set_disabled(state, user, target, true).await
+}
+
+pub async fn enable_user(
+    state: State<Arc<AppState>>,
+    user: AuthUser,
+    target: Path<Uuid>,
+) -> Result<Json<UserResponse>, ApiError> {
+    set_disabled(state, user, target, false).await
+}
+
+async fn set_disabled(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+    disabled: bool,
+) -> Result<Json<UserResponse>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+    if target == user.user_id {
+        return Err(ApiError::BadRequest(
+            "admins cannot disable their own account".to_string(),
+        ));
+    }
+
+    let updated = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users
+        SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, NOW()) ELSE NULL END
+        WHERE id = $1
+        RETURNING id, username, password_hash, role, created_at, disabled_at
+        "#,
+    )
+    .bind(target)
+    .bind(disabled)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "failed to update account status");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::NotFound)?;
+
+    if disabled && let Err(err) = revocation::revoke_all_for_user(&state.db_pool, target).await {
+        error!(?err, user_id = %target, "failed to revoke sessions of disabled user");
+    }
+
+    tracing::info!(admin_id = %user.user_id, user_id = %target, disabled, "changed account status");
+
+    Ok(Json(updated.into()))
+}
+
+pub async fn list_lockouts(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<Lockout>>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let lockouts = throttle::active_lockouts(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to list lockouts");
+            ApiError::Internal
+        })?;
+
+    Ok(Json(lockouts))
+}
+
+pub async fn unlock_user(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(target): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(target)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|_| ApiError::Internal)?
+        .ok_or(ApiError::NotFound)?;
+
+    throttle::unlock_user(&state.db_pool, &username, user.user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to unlock user");
+            ApiError::Internal
+        })?;
+
+    tracing::info!(admin_id = %user.user_id, user_id = %target, "unlocked account");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn query_audit_log(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let records = audit::search(&state.db_pool, &query).await.map_err(|e| {
+        tracing::error!(?e, "failed to query audit log");
+        ApiError::Internal
+    })?;
+
+    match query.format.as_deref() {
+        None | Some("json") => Ok(Json(records).into_response()),
+        Some("jsonl") => {
+            let mut body = String::new();
+            for record in &records {
+                body.push_str(&serde_json::to_string(record).map_err(|_| ApiError::Internal)?);
+                body.push('\n');
+            }
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/x-ndjson"),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"audit.jsonl\"",
+                    ),
+                ],
+                body,
+            )
+                .into_response())
+        }
+        Some(_) => Err(ApiError::BadRequest(
+            "format must be json or jsonl".to_string(),
+        )),
+    }
+}
+
+pub async fn jwks(State(state): State<Arc<AppState>>) -> Json<JwkSet> {
+    Json(state.jwt().keys.jwks().clone())
+}
+
+pub async fn issue_client_token(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(payload): Form<TokenRequest>,
+) -> Result<Json<ServiceTokenResponse>, ApiError> {
+    if payload.grant_type != "client_credentials" {
+        return Err(ApiError::BadRequest("unsupported_grant_type".to_string()));
+    }
+
+    let (client_id, client_secret) = match basic_credentials(&headers) {
+        Some(credentials) => credentials,
+        None => (
+            payload.client_id.ok_or(ApiError::BadCredentials)?,
+            payload.client_secret.ok_or(ApiError::BadCredentials)?,
+        ),
+    };
+
+    let client = clients::authenticate(&state.db_pool, &client_id, &client_secret).await?;
+
+    let scopes = match payload.scope.as_deref() {
+        None => client.scopes.clone(),
+        Some(requested) => {
+            let requested: Vec<String> = requested.split_whitespace().map(String::from).collect();
+            if requested.iter().any(|scope| !client.scopes.contains(scope)) {
+                return Err(ApiError::BadRequest("invalid_scope".to_string()));
+            }
+            requested
+        }
+    };
+
+    let jwt = state.jwt();
+    let cfg = &jwt.config;
+    let access_token = sign_service(&client, &scopes, &jwt)?;
+
+    tracing::info!(client_id = %client.client_id, "issued service token");
+
+    Ok(Json(ServiceTokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: cfg.service_ttl_seconds,
+        scope: scopes.join(" "),
+    }))
+}
+
+pub async fn list_clients(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<ServiceClient>>, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let clients = clients::list(&state.db_pool).await.map_err(|e| {
+        tracing::error!(?e, "failed to list service clients");
+        ApiError::Internal
+    })?;
+
+    Ok(Json(clients))
+}
+
+pub async fn create_client(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<CreateClientRequest>,
+) -> Result<(StatusCode, Json<CreatedClientResponse>), ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let valid_id = !payload.client_id.is_empty()
+        && payload
+            .client_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_id {
+        return Err(ApiError::BadRequest(
+            "client_id may only contain letters, digits, '-', '_' and '.'".to_string(),
+        ));
+    }
+    if let Some(unknown) = payload.scopes.iter().find(|scope| !scopes::is_known(scope)) {
+        return Err(ApiError::BadRequest(format!("unknown scope: {unknown}")));
+    }
+
+    let (client, client_secret) = clients::create(
+        &state.db_pool,
+        &payload.client_id,
+        &payload.scopes,
+        user.user_id,
+    )
+    .await?;
+
+    tracing::info!(admin_id = %user.user_id, client_id = %client.client_id, "created service client");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedClientResponse {
+            client,
+            client_secret,
+        }),
+    ))
+}
+
+pub async fn delete_client(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(client_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    user.require_scope(scopes::USERS_ADMIN)?;
+
+    let deleted = clients::delete(&state.db_pool, &client_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to delete service client");
+            ApiError::Internal
+        })?;
+    if !deleted {
+        return Err(ApiError::NotFound);
+    }
+
+    tracing::info!(admin_id = %user.user_id, %client_id, "deleted service client");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<api_keys::ApiKey>>, ApiError> {
+    let keys = api_keys::list(&state.db_pool, user.user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to list api keys");
+            ApiError::Internal
+        })?;
+
+    Ok(Json(keys))
+}
+
+pub async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKeyResponse>), ApiError> {
+    if user.role == UserRole::Service {
+        return Err(ApiError::Forbidden);
+    }
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(ApiError::BadRequest(
+            "name must be between 1 and 64 characters".to_string(),
+        ));
+    }
+
+    let allowed = scopes::for_role(user.role);
+    let key_scopes = match payload.scopes {
+        None => allowed.iter().map(|scope| scope.to_string()).collect(),
+        Some(requested) => {
+            if let Some(denied) = requested
+                .iter()
+                .find(|scope| !allowed.contains(&scope.as_str()))
+            {
+                return Err(ApiError::BadRequest(format!("scope not allowed: {denied}")));
+            }
+            requested
+        }
+    };
+
+    let expires_at = match payload.expires_in_days {
+        None => None,
+        Some(days @ 1..=api_keys::MAX_EXPIRES_IN_DAYS) => Some(
+            chrono::Utc::now()
+                .checked_add_signed(chrono::Duration::days(days))
+                .ok_or_else(|| {
+                    ApiError::BadRequest("expires_in_days is out of range".to_string())
+                })?,
+        ),
+        Some(_) => {
+            return Err(ApiError::BadRequest(format!(
+                "expires_in_days must be between 1 and {}",
+                api_keys::MAX_EXPIRES_IN_DAYS
+            )));
+        }
+    };
+
+    let (record, api_key) =
+        api_keys::create(&state.db_pool, user.user_id, name, &key_scopes, expires_at).await?;
+
+    tracing::info!(user_id = %user.user_id, key_id = %record.id, "created api key");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiKeyResponse { record, api_key }),
+    ))
+}
+
+pub async fn delete_api_key(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = api_keys::delete(&state.db_pool, user.user_id, id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to delete api key");
+            ApiError::Internal
+        })?;
+
+    if !deleted {
+        return Err(ApiError::NotFound);
+    }
+
+    tracing::info!(user_id = %user.user_id, key_id = %id, "revoked api key");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<Session>>, ApiError> {
+    let mut list = sessions::list(&state.db_pool, user.user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to list sessions");
+            ApiError::Internal
+        })?;
+
+    let current = user.claims.session_id();
+    for session in &mut list {
+        session.current = Some(session.id) == current;
+    }
+
+    Ok(Json(list))
+}
+
+pub async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let revoked = sessions::revoke(&state.db_pool, user.user_id, id)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to revoke session");
+            ApiError::Internal
+        })?;
+
+    if !revoked {
+        return Err(ApiError::NotFound);
+    }
+
+    tracing::info!(user_id = %user.user_id, session_id = %id, "revoked session");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn verify_token(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    if let Some(key) = extract_api_key(&headers) {
+        return verify_api_key(&state, key).await;
+    }
+
+    let token = extract_bearer(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let (user_id, claims) = active_claims(&state, token)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "token status lookup failed");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if let Some(session_id) = claims.session_id()
+        && let Err(e) = sessions::touch(&state.db_pool, session_id).await
+    {
+        tracing::warn!(?e, "failed to record session activity");
+    }
+
+    let mut out = forwarded_headers(user_id, claims.role, &claims.scopes())?;
+
+    if let Some(client_id) = &claims.client_id {
+        out.insert(
+            "X-Client-Id",
+            client_id.parse().map_err(|_| StatusCode::UNAUTHORIZED)?,
+        );
+    }
+    if let Some(actor_id) = claims.actor_id() {
+        out.insert("X-Impersonated-By", actor_id.to_string().parse().unwrap());
+    }
+    Ok((StatusCode::OK, out))
+}
+
+/// RFC 7662 token introspection for callers outside the gateway. Accepts
+/// access tokens and API keys and applies the same checks as `/verify`.
+pub async fn introspect(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(payload): Form<IntrospectRequest>,
+) -> Result<Json<IntrospectResponse>, ApiError> {
+    let (client_id, client_secret) = match basic_credentials(&headers) {
+        Some(credentials) => credentials,
+        None => (
+            payload.client_id.ok_or(ApiError::BadCredentials)?,
+            payload.client_secret.ok_or(ApiError::BadCredentials)?,
+        ),
+    };
+    clients::authenticate(&state.db_pool, &client_id, &client_secret).await?;
+
+    if api_keys::is_api_key(&payload.token) {
+        let owner = api_keys::lookup(&state.db_pool, &payload.token)
+            .await
+            .map_err(|e| {
+                tracing::error!(?e, "api key lookup failed");
+                ApiError::Internal
+            })?;
+
+        return Ok(Json(match owner {
+            Some(owner) => IntrospectResponse {
+                active: true,
+                token_type: Some("api_key"),
+                sub: Some(owner.user_id.to_string()),
+                role: Some(owner.role),
+                scope: Some(owner.effective_scopes().join(" ")),
+                ..Default::default()
+            },
+            None => IntrospectResponse::default(),
+        }));
+    }
+
+    let claims = active_claims(&state, &payload.token).await.map_err(|e| {
+        tracing::error!(?e, "token status lookup failed");
+        ApiError::Internal
+    })?;
+
+    Ok(Json(match claims {
+        Some((_, claims)) => IntrospectResponse {
+            active: true,
+            token_type: Some("Bearer"),
+            role: Some(claims.role),
+            scope: Some(claims.scopes().join(" ")),
+            client_id: claims.client_id,
+            sid: claims.sid,
+            act: claims.act,
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            sub: Some(claims.sub),
+        },
+        None => IntrospectResponse::default(),
+    }))
+}
+
+/// Claims of an access token that is validly signed, unexpired and not
+/// revoked, and whose impersonating admin (if any) is still an active admin.
+/// `None` for every token that must be rejected.
+async fn active_claims(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<(Uuid, Claims)>, sqlx::Error> {
+    let Ok(claims) = verify(token, &state.jwt()) else {
+        return Ok(None);
+    };
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return Ok(None);
+    };
+
+    if revocation::is_revoked(&state.db_pool, user_id, &claims).await? {
+        return Ok(None);
+    }
+
+    if claims.act.is_some() {
+        let Some(actor_id) = claims.actor_id() else {
+            return Ok(None);
+        };
+        if !is_active_admin(state, actor_id).await? {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some((user_id, claims)))
+}
+
+/// An impersonation token stops working as soon as its admin is demoted,
+/// disabled or deleted.
+async fn is_active_admin(state: &AppState, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM users WHERE id = $1 AND role = $2 AND disabled_at IS NULL)",
+    )
+    .bind(user_id)
+    .bind(UserRole::Admin)
+    .fetch_one(&state.db_pool)
+    .await
+}
+
+async fn verify_api_key(
+    state: &AppState,
+    key: &str,
+) -> Result<(StatusCode, HeaderMap), StatusCode> {
+    let owner = api_keys::authenticate(&state.db_pool, key)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "api key lookup failed");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let out = forwarded_headers(owner.user_id, owner.role, &owner.effective_scopes())?;
+    Ok((StatusCode::OK, out))
+}
+
+/// Identity headers Traefik copies onto the upstream request.
+fn forwarded_headers(
+    user_id: Uuid,
+    role: UserRole,
+    scopes: &[&str],
+) -> Result<HeaderMap, StatusCode> {
+    let mut out = HeaderMap::new();
+
+    out.insert("X-User-Id", user_id.to_string().parse().unwrap());
+
+    out.insert("X-User-Role", role.to_string().parse().unwrap());
+
+    out.insert(
+        "X-Scopes",
+        scopes
+            .join(" ")
+            .parse()
+            .map_err(|_| StatusCode::UNAUTHORIZED)?,
+    );
+    Ok(out)
+}
+
+async fn start_session(
+    state: &AppState,
+    user_id: Uuid,
+    client: &ClientInfo,
+    cfg: &JwtConfig,
+) -> Result<(Uuid, String), ApiError> {
+    sessions::start(&state.db_pool, user_id, client, cfg.refresh_ttl_seconds)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to start session");
+            ApiError::Internal
+        })
+}
+
+/// Removes the auth user, which cascades to its tokens and MFA state, and
+/// tells user-svc and device-svc to purge their data in the same transaction.
+async fn delete_user_account(
+    state: &AppState,
+    user_id: Uuid,
+    deleted_by: Uuid,
+) -> Result<(), ApiError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    sqlx::query_scalar::<_, Uuid>("DELETE FROM users WHERE id = $1 RETURNING id")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to delete user");
+            ApiError::Internal
+        })?
+        .ok_or(ApiError::NotFound)?;
+
+    let event = UserDeletedEvent {
+        event_type: "USER_DELETED",
+        user_id,
+        payload: DeletionPayload {
+            id: user_id,
+            deleted_by,
+        },
+    };
+    for queue in [&state.user_events_queue, &state.device_events_queue] {
+        outbox::enqueue(&mut tx, queue, &event).await.map_err(|e| {
+            tracing::error!(?e, "failed to enqueue USER_DELETED event");
+            ApiError::Internal
+        })?;
+    }
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    tracing::info!(%user_id, %deleted_by, "deleted user account");
+
+    Ok(())
+}
+
+async fn revoke_sessions_after_password_change(state: &AppState, user_id: Uuid) {
+    if let Err(err) = revocation::revoke_all_for_user(&state.db_pool, user_id).await {
+        error!(?err, %user_id, "failed to revoke sessions after password change");
+    }
+}
+
+fn auth_response(access_token: String, refresh_token: String, cfg: &JwtConfig) -> AuthResponse {
+    AuthResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: cfg.ttl_seconds,
+        refresh_token,
+    }
+}
+
+/// Client credentials from an `Authorization: Basic` header, if present.
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded = headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+    Some((id.to_string(), secret.to_string()))
+}
+
+fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
+    let auth = headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, key) = auth.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("apikey") {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+fn extract_bearer(headers: &HeaderMap) -> Option<&str> {
+    let auth = headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, token) = auth.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Replaces a hash made with outdated Argon2 parameters after a successful
+/// login. Skipped if the password changed in the meantime; failures only
+/// log, the login goes ahead either way.
+async fn rehash_password(state: &AppState, user: &User, password: &str) {
+    let hash = match state.passwords.hash(password).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!(?e, user_id = %user.id, "failed to rehash password");
+            return;
+        }
+    };
+
+    match sqlx::query("UPDATE users SET password_hash = $3 WHERE id = $1 AND password_hash = $2")
+        .bind(user.id)
+        .bind(&user.password_hash)
+        .bind(&hash)
+        .execute(&state.db_pool)
+        .await
+    {
+        Ok(_) => tracing::info!(user_id = %user.id, "upgraded password hash"),
+        Err(e) => tracing::error!(?e, user_id = %user.id, "failed to store rehashed password"),
+    }
 }
@@ -1,12 +1,17 @@
+use std::{collections::HashMap, sync::Arc};
+
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{Algorithm, Header, Validation};
 use time::{Duration, OffsetDateTime};
 
-use crate::{config::JwtConfig, errors::ApiError, user::UserRole};
+use crate::{
+    AppState, clients::ServiceClient, config::JwtConfig, errors::ApiError, keys::KeyStore,
+    revocation, scopes, user::UserRole,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -15,12 +20,160 @@ pub struct Claims {
     pub aud: String,
     pub exp: i64,
     pub iat: i64,
+    pub jti: String,
     pub role: UserRole,
+    /// Space-separated permission scopes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Session the token was issued for; absent on service tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// Set when the token was issued through the client-credentials grant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Admin acting as `sub` on an impersonation token (RFC 8693 `act`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Actor {
+    pub sub: String,
+}
+
+impl Claims {
+    /// Granted scopes. User tokens issued before scopes existed fall back to
+    /// the defaults of their role.
+    pub fn scopes(&self) -> Vec<&str> {
+        match self.scope.as_deref() {
+            Some(scope) => scope.split_whitespace().collect(),
+            None => scopes::for_role(self.role).to_vec(),
+        }
+    }
+
+    pub fn session_id(&self) -> Option<Uuid> {
+        self.sid
+            .as_deref()
+            .and_then(|sid| Uuid::parse_str(sid).ok())
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes().contains(&scope)
+    }
+
+    /// The impersonating admin, if this is an impersonation token.
+    pub fn actor_id(&self) -> Option<Uuid> {
+        self.act
+            .as_ref()
+            .and_then(|act| Uuid::parse_str(&act.sub).ok())
+    }
+}
+
+/// Token settings, keys and the matching `Validation`s, built once at
+/// startup and replaced as a whole on reload.
+pub struct JwtState {
+    pub config: JwtConfig,
+    pub keys: KeyStore,
+    validations: HashMap<Algorithm, Validation>,
+}
+
+impl JwtState {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self::new(JwtConfig::from_env()?, KeyStore::from_env()?))
+    }
+
+    /// Rereads the key directory and the signing kid. The token settings come
+    /// from the environment, which cannot change while the process runs, so
+    /// they are kept.
+    pub fn reload(&self) -> anyhow::Result<Self> {
+        Ok(Self::new(self.config.clone(), KeyStore::from_env()?))
+    }
+
+    fn new(config: JwtConfig, keys: KeyStore) -> Self {
+        let validations = keys
+            .algorithms()
+            .map(|alg| {
+                let mut validation = Validation::new(alg);
+                validation.set_audience(std::slice::from_ref(&config.audience));
+                validation.set_issuer(std::slice::from_ref(&config.issuer));
+                validation.validate_exp = true;
+                (alg, validation)
+            })
+            .collect();
+
+        Self {
+            config,
+            keys,
+            validations,
+        }
+    }
+}
+
+pub fn sign(
+    user_id: Uuid,
+    role: UserRole,
+    session_id: Uuid,
+    jwt: &JwtState,
+) -> Result<String, ApiError> {
+    let cfg = &jwt.config;
+    let now = OffsetDateTime::now_utc();
+    let exp = now + Duration::seconds(cfg.ttl_seconds);
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        iss: cfg.issuer.clone(),
+        aud: cfg.audience.clone(),
+        exp: exp.unix_timestamp(),
+        iat: now.unix_timestamp(),
+        jti: Uuid::new_v4().to_string(),
+        role,
+        scope: Some(scopes::for_role(role).join(" ")),
+        sid: Some(session_id.to_string()),
+        client_id: None,
+        act: None,
+    };
+
+    encode(&claims, &jwt.keys)
 }
 
-pub fn sign(user_id: Uuid, role: UserRole, cfg: &JwtConfig) -> Result<String, ApiError> {
+/// Signs an access token for a service client with the granted `scopes`.
+pub fn sign_service(
+    client: &ServiceClient,
+    scopes: &[String],
+    jwt: &JwtState,
+) -> Result<String, ApiError> {
+    let cfg = &jwt.config;
     let now = OffsetDateTime::now_utc();
-    let exp = now + Duration::seconds(cfg.ttl_seconds as i64);
+    let exp = now + Duration::seconds(cfg.service_ttl_seconds);
+
+    let claims = Claims {
+        sub: client.id.to_string(),
+        iss: cfg.issuer.clone(),
+        aud: cfg.audience.clone(),
+        exp: exp.unix_timestamp(),
+        iat: now.unix_timestamp(),
+        jti: Uuid::new_v4().to_string(),
+        role: UserRole::Service,
+        scope: Some(scopes.join(" ")),
+        sid: None,
+        client_id: Some(client.client_id.clone()),
+        act: None,
+    };
+
+    encode(&claims, &jwt.keys)
+}
+
+/// Signs a short-lived token that lets `admin_id` act as `user_id` with the
+/// user's own scopes. It belongs to no session and cannot be refreshed.
+pub fn sign_impersonation(
+    user_id: Uuid,
+    role: UserRole,
+    admin_id: Uuid,
+    jwt: &JwtState,
+) -> Result<String, ApiError> {
+    let cfg = &jwt.config;
+    let now = OffsetDateTime::now_utc();
+    let exp = now + Duration::seconds(cfg.impersonation_ttl_seconds);
 
     let claims = Claims {
         sub: user_id.to_string(),
@@ -28,51 +181,73 @@ pub fn sign(user_id: Uuid, role: UserRole, cfg: &JwtConfig) -> Result<String, Ap
         aud: cfg.audience.clone(),
         exp: exp.unix_timestamp(),
         iat: now.unix_timestamp(),
+        jti: Uuid::new_v4().to_string(),
         role,
+        scope: Some(scopes::for_role(role).join(" ")),
+        sid: None,
+        client_id: None,
+        act: Some(Actor {
+            sub: admin_id.to_string(),
+        }),
     };
 
-    jsonwebtoken::encode(
-        &Header::new(Algorithm::HS256),
-        &claims,
-        &EncodingKey::from_secret(cfg.secret.as_bytes()),
-    )
-    .map_err(|_| ApiError::Internal)
+    encode(&claims, &jwt.keys)
+}
+
+fn encode(claims: &Claims, keys: &KeyStore) -> Result<String, ApiError> {
+    let (kid, key, alg) = keys.signing();
+    let mut header = Header::new(alg);
+    header.kid = Some(kid.to_string());
+
+    jsonwebtoken::encode(&header, claims, key).map_err(|_| ApiError::Internal)
 }
 
-pub fn verify(token: &str, cfg: &JwtConfig) -> Result<Claims, ApiError> {
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.set_audience(&[cfg.audience.clone()]);
-    validation.set_issuer(&[cfg.issuer.clone()]);
-    validation.validate_exp = true;
-
-    jsonwebtoken::decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(cfg.secret.as_bytes()),
-        &validation,
-    )
-    .map(|data| data.claims)
-    .map_err(|_| ApiError::BadCredentials)
+pub fn verify(token: &str, jwt: &JwtState) -> Result<Claims, ApiError> {
+    let header = jsonwebtoken::decode_header(token).map_err(|_| ApiError::BadCredentials)?;
+    let key = header
+        .kid
+        .as_deref()
+        .and_then(|kid| jwt.keys.verification_key(kid))
+        .ok_or(ApiError::BadCredentials)?;
+    let validation = jwt
+        .validations
+        .get(&key.alg)
+        .ok_or(ApiError::BadCredentials)?;
+
+    jsonwebtoken::decode::<Claims>(token, &key.key, validation)
+        .map(|data| data.claims)
+        .map_err(|_| ApiError::BadCredentials)
 }
 
 pub struct AuthUser {
     pub user_id: Uuid,
+    pub role: UserRole,
+    pub claims: Claims,
 }
 
-impl<S> FromRequestParts<S> for AuthUser
-where
-    S: Send + Sync,
-{
+impl AuthUser {
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        if self.claims.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden)
+        }
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for AuthUser {
     type Rejection = ApiError;
 
     fn from_request_parts(
         parts: &mut Parts,
-        _state: &S,
+        state: &Arc<AppState>,
     ) -> impl Future<Output = Result<Self, Self::Rejection>> + Send {
         let header_opt = parts
             .headers
             .get(axum::http::header::AUTHORIZATION)
             .and_then(|v| v.to_str().ok())
             .map(|v| v.to_string());
+        let state = state.clone();
 
         async move {
             let auth_header = header_opt.ok_or(ApiError::BadCredentials)?;
@@ -80,10 +255,27 @@ where
                 .strip_prefix("Bearer ")
                 .ok_or(ApiError::BadCredentials)?;
 
-            let cfg = JwtConfig::from_env();
-            let claims = verify(token, &cfg).map_err(|_| ApiError::BadCredentials)?;
+            let claims = verify(token, &state.jwt()).map_err(|_| ApiError::BadCredentials)?;
             let user_id = Uuid::parse_str(&claims.sub).map_err(|_| ApiError::BadCredentials)?;
-            Ok(AuthUser { user_id })
+
+            // Impersonation only reaches the other services through /verify;
+            // it must not change the user's password, sessions or API keys.
+            if claims.act.is_some() {
+                return Err(ApiError::Forbidden);
+            }
+
+            if revocation::is_revoked(&state.db_pool, user_id, &claims)
+                .await
+                .map_err(|_| ApiError::Internal)?
+            {
+                return Err(ApiError::BadCredentials);
+            }
+
+            Ok(AuthUser {
+                user_id,
+                role: claims.role,
+                claims,
+            })
         }
     }
 }
@@ -0,0 +1,231 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ed25519_dalek::{
+    SigningKey, VerifyingKey,
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use rsa::{RsaPrivateKey, RsaPublicKey, pkcs1::DecodeRsaPrivateKey, traits::PublicKeyParts};
+
+/// Signing and verification keys, loaded from a directory of PEM files at
+/// startup and again on `SIGHUP`.
+///
+/// Every `<kid>.pem` file holds a PKCS#8 (or PKCS#1 for RSA) private key;
+/// `<kid>.pub.pem` files hold public keys that are only used for
+/// verification, e.g. a retired signing key whose tokens have not expired yet.
+/// The algorithm is inferred from the key type: Ed25519 keys sign with EdDSA,
+/// RSA keys with RS256. The kid to sign with is read from the `signing_kid`
+/// file in the same directory, falling back to `JWT_SIGNING_KID`.
+pub struct KeyStore {
+    signing_kid: String,
+    signing_key: EncodingKey,
+    signing_alg: Algorithm,
+    verification: HashMap<String, VerificationKey>,
+    jwks: JwkSet,
+}
+
+const SIGNING_KID_FILE: &str = "signing_kid";
+
+pub struct VerificationKey {
+    pub alg: Algorithm,
+    pub key: DecodingKey,
+}
+
+struct LoadedKey {
+    kid: String,
+    alg: Algorithm,
+    encoding: Option<EncodingKey>,
+    jwk: Jwk,
+}
+
+impl KeyStore {
+    pub fn from_env() -> Result<Self> {
+        let dir = std::env::var("JWT_KEYS_DIR").unwrap_or_else(|_| "/app/keys".into());
+        let dir = Path::new(&dir);
+        Self::load(dir, signing_kid(dir)?)
+    }
+
+    pub fn load(dir: &Path, signing_kid: String) -> Result<Self> {
+        let mut signing = None;
+        let mut verification = HashMap::new();
+        let mut jwks = JwkSet { keys: Vec::new() };
+
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("failed to read JWT key directory {}", dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some(stem) = file_name.strip_suffix(".pem") else {
+                continue;
+            };
+
+            let pem = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let loaded = match stem.strip_suffix(".pub") {
+                Some(kid) => load_public(kid, &pem),
+                None => load_private(stem, &pem),
+            }
+            .with_context(|| format!("invalid JWT key {}", path.display()))?;
+
+            let key = DecodingKey::from_jwk(&loaded.jwk)?;
+            if verification.contains_key(&loaded.kid) {
+                bail!("duplicate JWT key id {}", loaded.kid);
+            }
+            verification.insert(
+                loaded.kid.clone(),
+                VerificationKey {
+                    alg: loaded.alg,
+                    key,
+                },
+            );
+            jwks.keys.push(loaded.jwk);
+
+            if loaded.kid == signing_kid {
+                let encoding = loaded
+                    .encoding
+                    .ok_or_else(|| anyhow!("signing key {} has no private key", signing_kid))?;
+                signing = Some((encoding, loaded.alg));
+            }
+        }
+
+        let (signing_key, signing_alg) = signing
+            .ok_or_else(|| anyhow!("signing key {} not found in {}", signing_kid, dir.display()))?;
+
+        tracing::info!(
+            kid = %signing_kid,
+            alg = ?signing_alg,
+            keys = verification.len(),
+            "loaded JWT keys"
+        );
+
+        Ok(Self {
+            signing_kid,
+            signing_key,
+            signing_alg,
+            verification,
+            jwks,
+        })
+    }
+
+    pub fn signing(&self) -> (&str, &EncodingKey, Algorithm) {
+        (&self.signing_kid, &self.signing_key, self.signing_alg)
+    }
+
+    pub fn verification_key(&self, kid: &str) -> Option<&VerificationKey> {
+        self.verification.get(kid)
+    }
+
+    /// Algorithms of all verification keys.
+    pub fn algorithms(&self) -> impl Iterator<Item = Algorithm> + '_ {
+        self.verification.values().map(|key| key.alg)
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+/// Contents of `<dir>/signing_kid` if the file exists, else `JWT_SIGNING_KID`.
+fn signing_kid(dir: &Path) -> Result<String> {
+    let path = dir.join(SIGNING_KID_FILE);
+    let kid = match fs::read_to_string(&path) {
+        Ok(kid) => kid.trim().to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => std::env::var("JWT_SIGNING_KID")
+            .with_context(|| {
+                format!(
+                    "JWT_SIGNING_KID is required when {} is absent.",
+                    path.display()
+                )
+            })?,
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    if kid.is_empty() {
+        bail!("the signing kid must not be empty.");
+    }
+    Ok(kid)
+}
+
+fn load_private(kid: &str, pem: &str) -> Result<LoadedKey> {
+    if let Ok(key) = SigningKey::from_pkcs8_pem(pem) {
+        return Ok(LoadedKey {
+            kid: kid.to_string(),
+            alg: Algorithm::EdDSA,
+            encoding: Some(EncodingKey::from_ed_pem(pem.as_bytes())?),
+            jwk: ed25519_jwk(kid, &key.verifying_key()),
+        });
+    }
+
+    let key = RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|_| anyhow!("expected an Ed25519 or RSA private key"))?;
+
+    Ok(LoadedKey {
+        kid: kid.to_string(),
+        alg: Algorithm::RS256,
+        encoding: Some(EncodingKey::from_rsa_pem(pem.as_bytes())?),
+        jwk: rsa_jwk(kid, &key.to_public_key()),
+    })
+}
+
+fn load_public(kid: &str, pem: &str) -> Result<LoadedKey> {
+    if let Ok(key) = VerifyingKey::from_public_key_pem(pem) {
+        return Ok(LoadedKey {
+            kid: kid.to_string(),
+            alg: Algorithm::EdDSA,
+            encoding: None,
+            jwk: ed25519_jwk(kid, &key),
+        });
+    }
+
+    let key = RsaPublicKey::from_public_key_pem(pem)
+        .map_err(|_| anyhow!("expected an Ed25519 or RSA public key"))?;
+
+    Ok(LoadedKey {
+        kid: kid.to_string(),
+        alg: Algorithm::RS256,
+        encoding: None,
+        jwk: rsa_jwk(kid, &key),
+    })
+}
+
+fn ed25519_jwk(kid: &str, key: &VerifyingKey) -> Jwk {
+    Jwk {
+        common: common_parameters(kid, KeyAlgorithm::EdDSA),
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+        }),
+    }
+}
+
+fn rsa_jwk(kid: &str, key: &RsaPublicKey) -> Jwk {
+    Jwk {
+        common: common_parameters(kid, KeyAlgorithm::RS256),
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+        }),
+    }
+}
+
+fn common_parameters(kid: &str, alg: KeyAlgorithm) -> CommonParameters {
+    CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_algorithm: Some(alg),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    }
+}
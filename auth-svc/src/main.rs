@@ -1,61 +1,164 @@
-use std::sync::Arc;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
 
-use axum::{Router, routing::get};
+use axum::Router;
 use routes::create_routes;
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod messaging;
 
-mod auth;
+mod api_keys;
+mod audit;
+mod cli;
+mod clients;
 mod config;
 mod errors;
 mod handlers;
 mod jwt;
+mod keys;
+mod mfa;
+mod password_reset;
+mod passwords;
+mod provisioning;
+mod refresh;
+mod revocation;
 mod routes;
+mod scopes;
+mod secrets;
+mod sessions;
+mod throttle;
 mod user;
 
-use messaging::EventPublisher;
+use config::{MfaConfig, ThrottleConfig};
+use jwt::JwtState;
+use outbox::EventPublisher;
+use passwords::Passwords;
 
 pub struct AppState {
     db_pool: PgPool,
-    publisher: EventPublisher,
+    user_events_queue: String,
+    device_events_queue: String,
+    jwt: RwLock<Arc<JwtState>>,
+    passwords: Passwords,
+    throttle: ThrottleConfig,
+    mfa: MfaConfig,
+    password_reset_ttl_seconds: i64,
 }
 
+impl AppState {
+    /// Current token settings and keys. Callers hold the returned snapshot
+    /// for the whole request so a reload never mixes old and new keys.
+    fn jwt(&self) -> Arc<JwtState> {
+        self.jwt.read().expect("jwt state lock poisoned").clone()
+    }
+}
+
+/// Reloads the signing keys and the signing kid from `JWT_KEYS_DIR` on every
+/// `SIGHUP`. A reload that fails validation is logged and the previous keys
+/// stay in use.
+fn spawn_jwt_reload(state: Arc<AppState>) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match state.jwt().reload() {
+                Ok(jwt) => {
+                    *state.jwt.write().expect("jwt state lock poisoned") = Arc::new(jwt);
+                    tracing::info!("reloaded jwt keys");
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "jwt reload failed, keeping previous keys")
+                }
+            }
+        }
+    }))
+}
+
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let admin_cli = args.next().as_deref() == Some("admin");
+
+    // The CLI prints its own results; keep routine logs out of them.
+    let default_filter = if admin_cli { "warn" } else { "info" };
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+            std::env::var("RUST_LOG").unwrap_or_else(|_| default_filter.into()),
         ))
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    if admin_cli {
+        if let Err(e) = cli::run(args.collect()).await {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
 
     let broker_url =
         std::env::var("SYNC_BROKER_URL").expect("SYNC_BROKER_URL must be set for auth-svc.");
-    let sync_queue = std::env::var("SYNC_QUEUE").unwrap_or_else(|_| "sync.events".into());
+    let user_events_queue =
+        std::env::var("USER_EVENTS_QUEUE").unwrap_or_else(|_| "user.events".into());
+    let device_events_queue =
+        std::env::var("DEVICE_EVENTS_QUEUE").unwrap_or_else(|_| "device.events".into());
+    let auth_events_queue =
+        std::env::var("AUTH_EVENTS_QUEUE").unwrap_or_else(|_| "auth.events".into());
+
+    let jwt = JwtState::from_env().map_err(|e| format!("invalid jwt configuration: {e:#}"))?;
+    let passwords =
+        Passwords::from_env().map_err(|e| format!("invalid password configuration: {e:#}"))?;
+    let throttle =
+        ThrottleConfig::from_env().map_err(|e| format!("invalid throttle configuration: {e:#}"))?;
 
     let pool = PgPoolOptions::new()
         .max_connections(20)
         .connect(&db_url)
         .await?;
 
-    let publisher = EventPublisher::new(broker_url, sync_queue).await?;
+    MIGRATOR.run(&pool).await?;
+
+    let publisher = EventPublisher::new(
+        broker_url.clone(),
+        vec![user_events_queue.clone(), device_events_queue.clone()],
+    );
+    let _relay_handle = outbox::spawn_relay(pool.clone(), publisher);
+
+    let _provisioning_handle =
+        provisioning::spawn_provisioning_consumer(broker_url, auth_events_queue, pool.clone());
 
     let shared_state = Arc::new(AppState {
         db_pool: pool,
-        publisher,
+        user_events_queue,
+        device_events_queue,
+        jwt: RwLock::new(Arc::new(jwt)),
+        passwords,
+        throttle,
+        mfa: MfaConfig::from_env(),
+        password_reset_ttl_seconds: password_reset::ttl_seconds_from_env(),
     });
 
+    let _reload_handle = spawn_jwt_reload(shared_state.clone())?;
+
     let app = Router::new()
         .merge(create_routes())
         .with_state(shared_state.clone());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
     tracing::info!("listening on 8080");
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,423 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::{
+    config::MfaConfig,
+    errors::ApiError,
+    secrets::{generate_token, hash_token},
+};
+
+const STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+/// Outcome of a successfully completed login challenge.
+pub struct CompletedChallenge {
+    pub user_id: Uuid,
+    /// Set when the challenge also finished a first-time enrollment.
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TotpRow {
+    secret: String,
+    enabled: bool,
+    last_used_step: Option<i64>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ChallengeRow {
+    id: Uuid,
+    user_id: Uuid,
+    expired: bool,
+    completed: bool,
+    attempts: i32,
+}
+
+pub async fn is_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL)",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Returns the pending secret for `user_id`, storing a fresh one when there
+/// is none or it has expired. A pending secret is handed out again rather
+/// than replaced, so logging in twice does not invalidate an authenticator
+/// the user has just set up. Fails with `Conflict` when TOTP is already
+/// active; disabling it first is required to re-enroll.
+pub async fn begin_enrollment(
+    pool: &PgPool,
+    user_id: Uuid,
+    username: &str,
+    cfg: &MfaConfig,
+) -> Result<TotpEnrollment, ApiError> {
+    let mut secret = [0u8; 20];
+    rand::rng().fill_bytes(&mut secret);
+    let encoded = build_totp(secret.to_vec(), username, cfg)?.get_secret_base32();
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_totp (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+        SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+        WHERE user_totp.enabled_at IS NULL AND user_totp.created_at <= $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(&encoded)
+    .bind(Utc::now() - Duration::seconds(cfg.enrollment_ttl_seconds))
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "failed to store totp secret");
+        ApiError::Internal
+    })?;
+
+    let (secret, enabled) = sqlx::query_as::<_, (String, bool)>(
+        "SELECT secret, enabled_at IS NOT NULL FROM user_totp WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|_| ApiError::Internal)?;
+
+    if enabled {
+        return Err(ApiError::Conflict);
+    }
+
+    let bytes = Secret::Encoded(secret.clone())
+        .to_bytes()
+        .map_err(|_| ApiError::Internal)?;
+    Ok(TotpEnrollment {
+        provisioning_uri: build_totp(bytes, username, cfg)?.get_url(),
+        secret,
+    })
+}
+
+/// Confirms a pending enrollment with a first code and returns the recovery
+/// codes, which are only ever shown this once.
+pub async fn activate(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+    cfg: &MfaConfig,
+) -> Result<Vec<String>, ApiError> {
+    let mut tx = pool.begin().await.map_err(|_| ApiError::Internal)?;
+    let codes = activate_in(&mut tx, user_id, code, cfg).await?;
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+    Ok(codes)
+}
+
+/// Checks a TOTP or recovery code for a user with TOTP enabled.
+pub async fn verify(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, ApiError> {
+    let mut conn = pool.acquire().await.map_err(|_| ApiError::Internal)?;
+    verify_in(&mut conn, user_id, code).await
+}
+
+pub async fn disable(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM totp_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
+
+/// Opens the second login step for `user_id` and returns its opaque token.
+pub async fn create_challenge(
+    pool: &PgPool,
+    user_id: Uuid,
+    cfg: &MfaConfig,
+) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(cfg.challenge_ttl_seconds);
+
+    sqlx::query(
+        r#"
+        INSERT INTO mfa_challenges (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Username behind a pending challenge, used to throttle wrong codes the
+/// same way as wrong passwords.
+pub async fn challenge_username(pool: &PgPool, token: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT u.username
+        FROM mfa_challenges c
+        JOIN users u ON u.id = c.user_id
+        WHERE c.token_hash = $1
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
+
+/// Completes a login challenge with a TOTP or recovery code. If the user was
+/// enrolling during this login, the code activates the pending secret.
+/// A challenge is burnt after `max_attempts` wrong codes.
+pub async fn complete_challenge(
+    pool: &PgPool,
+    token: &str,
+    code: &str,
+    cfg: &MfaConfig,
+) -> Result<CompletedChallenge, ApiError> {
+    let mut tx = pool.begin().await.map_err(|_| ApiError::Internal)?;
+
+    let challenge = sqlx::query_as::<_, ChallengeRow>(
+        r#"
+        SELECT id, user_id, expires_at <= NOW() AS expired, completed_at IS NOT NULL AS completed, attempts
+        FROM mfa_challenges
+        WHERE token_hash = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "db error on mfa challenge");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::BadCredentials)?;
+
+    if challenge.expired || challenge.completed || challenge.attempts >= cfg.max_attempts {
+        return Err(ApiError::BadCredentials);
+    }
+
+    let totp = load_totp(&mut tx, challenge.user_id).await?;
+    let outcome = match totp {
+        Some(row) if row.enabled => verify_in(&mut tx, challenge.user_id, code)
+            .await?
+            .then_some(None),
+        Some(_) => match activate_in(&mut tx, challenge.user_id, code, cfg).await {
+            Ok(codes) => Some(Some(codes)),
+            Err(ApiError::BadCredentials) => None,
+            Err(err) => return Err(err),
+        },
+        None => None,
+    };
+
+    let Some(recovery_codes) = outcome else {
+        sqlx::query("UPDATE mfa_challenges SET attempts = attempts + 1 WHERE id = $1")
+            .bind(challenge.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| ApiError::Internal)?;
+        tx.commit().await.map_err(|_| ApiError::Internal)?;
+        return Err(ApiError::BadCredentials);
+    };
+
+    sqlx::query("UPDATE mfa_challenges SET completed_at = NOW() WHERE id = $1")
+        .bind(challenge.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok(CompletedChallenge {
+        user_id: challenge.user_id,
+        recovery_codes,
+    })
+}
+
+async fn activate_in(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    code: &str,
+    cfg: &MfaConfig,
+) -> Result<Vec<String>, ApiError> {
+    let expired_before = Utc::now() - Duration::seconds(cfg.enrollment_ttl_seconds);
+    let row = load_totp(conn, user_id)
+        .await?
+        .filter(|row| !row.enabled && row.created_at > expired_before)
+        .ok_or(ApiError::NotFound)?;
+
+    let step = match_step(&row, code)?.ok_or(ApiError::BadCredentials)?;
+
+    sqlx::query("UPDATE user_totp SET enabled_at = NOW(), last_used_step = $2 WHERE user_id = $1")
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    sqlx::query("DELETE FROM totp_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let code = generate_recovery_code();
+        sqlx::query("INSERT INTO totp_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(hash_token(&code))
+            .execute(&mut *conn)
+            .await
+            .map_err(|_| ApiError::Internal)?;
+        codes.push(code);
+    }
+
+    Ok(codes)
+}
+
+async fn verify_in(conn: &mut PgConnection, user_id: Uuid, code: &str) -> Result<bool, ApiError> {
+    let Some(row) = load_totp(conn, user_id).await?.filter(|row| row.enabled) else {
+        return Ok(false);
+    };
+
+    if let Some(step) = match_step(&row, code)? {
+        // Each time step is accepted once, so an observed code cannot be replayed.
+        let updated = sqlx::query(
+            r#"
+            UPDATE user_totp SET last_used_step = $2
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+        return Ok(updated.rows_affected() == 1);
+    }
+
+    let used = sqlx::query(
+        r#"
+        UPDATE totp_recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash_token(&normalize_recovery_code(code)))
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| ApiError::Internal)?;
+
+    if used.rows_affected() == 1 {
+        tracing::info!(%user_id, "recovery code used");
+    }
+
+    Ok(used.rows_affected() == 1)
+}
+
+async fn load_totp(conn: &mut PgConnection, user_id: Uuid) -> Result<Option<TotpRow>, ApiError> {
+    sqlx::query_as::<_, TotpRow>(
+        r#"
+        SELECT secret, enabled_at IS NOT NULL AS enabled, last_used_step, created_at
+        FROM user_totp
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "failed to load totp secret");
+        ApiError::Internal
+    })
+}
+
+/// Returns the time step `code` was generated for, allowing one step of
+/// clock drift either way, skipping steps that were already used.
+fn match_step(row: &TotpRow, code: &str) -> Result<Option<i64>, ApiError> {
+    let code = code.trim();
+    if code.len() != 6 || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let secret = Secret::Encoded(row.secret.clone())
+        .to_bytes()
+        .map_err(|_| ApiError::Internal)?;
+    let totp = TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP_SECONDS,
+        secret,
+        None,
+        String::new(),
+    );
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| ApiError::Internal)?
+        .as_secs();
+    let current = now / STEP_SECONDS;
+
+    Ok([current - 1, current, current + 1]
+        .into_iter()
+        .filter(|step| row.last_used_step.is_none_or(|last| *step as i64 > last))
+        .find(|step| totp.generate(step * STEP_SECONDS) == code)
+        .map(|step| step as i64))
+}
+
+fn build_totp(secret: Vec<u8>, username: &str, cfg: &MfaConfig) -> Result<TOTP, ApiError> {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        STEP_SECONDS,
+        secret,
+        Some(cfg.issuer.replace(':', "")),
+        username.replace(':', ""),
+    )
+    .map_err(|e| {
+        tracing::error!(?e, "invalid totp parameters");
+        ApiError::Internal
+    })
+}
+
+fn generate_recovery_code() -> String {
+    let raw = generate_token();
+    format!("{}-{}", &raw[..5], &raw[5..10])
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    let compact: String = code
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if compact.len() == 10 {
+        format!("{}-{}", &compact[..5], &compact[5..])
+    } else {
+        compact
+    }
+}
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::ApiError,
+    secrets::{generate_token, hash_token},
+};
+
+pub struct IssuedReset {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Lifetime of a reset token, read once at startup.
+pub fn ttl_seconds_from_env() -> i64 {
+    std::env::var("PASSWORD_RESET_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(3600)
+}
+
+/// Issues a one-time reset token for `user_id` on behalf of `admin_id`,
+/// valid for `ttl_seconds`. Any reset token still pending for the user is
+/// discarded.
+pub async fn issue(
+    pool: &PgPool,
+    user_id: Uuid,
+    admin_id: Uuid,
+    ttl_seconds: i64,
+) -> Result<IssuedReset, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(ttl_seconds);
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO password_reset_tokens (user_id, token_hash, created_by, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(admin_id)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(IssuedReset { token, expires_at })
+}
+
+/// Username of the account a still-valid reset token belongs to, so the new
+/// password can be checked against the policy before the token is spent.
+pub async fn pending_username(pool: &PgPool, token: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT u.username
+        FROM password_reset_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1 AND t.used_at IS NULL AND t.expires_at > NOW()
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
+
+/// Redeems a reset token and stores `password_hash` for its user.
+/// Returns the id of the user whose password was replaced.
+pub async fn redeem(pool: &PgPool, token: &str, password_hash: &str) -> Result<Uuid, ApiError> {
+    let mut tx = pool.begin().await.map_err(|_| ApiError::Internal)?;
+
+    let user_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "db error on password reset");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::BadCredentials)?;
+
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok(user_id)
+}
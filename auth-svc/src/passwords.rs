@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result, bail};
+use argon2::{
+    ARGON2ID_IDENT, Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier,
+    Version,
+    password_hash::{self, SaltString, rand_core::OsRng},
+};
+
+use crate::errors::ApiError;
+
+/// Upper bound on accepted passwords, so a huge body cannot tie up the hasher.
+const MAX_LENGTH: usize = 256;
+
+/// Argon2 parameters and password policy, loaded once at startup.
+pub struct Passwords {
+    params: Params,
+    min_length: usize,
+    breached: HashSet<String>,
+}
+
+impl Passwords {
+    pub fn from_env() -> Result<Self> {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> Result<T> {
+            let value = std::env::var(name).unwrap_or_default();
+            if value.trim().is_empty() {
+                return Ok(default);
+            }
+            value
+                .trim()
+                .parse::<T>()
+                .ok()
+                .with_context(|| format!("{name} must be a number, got {value:?}."))
+        }
+
+        let params = Params::new(
+            var("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST)?,
+            var("ARGON2_ITERATIONS", Params::DEFAULT_T_COST)?,
+            var("ARGON2_PARALLELISM", Params::DEFAULT_P_COST)?,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("invalid Argon2 parameters: {e}"))?;
+
+        let min_length = var("PASSWORD_MIN_LENGTH", 8)?;
+        if min_length == 0 || min_length > MAX_LENGTH {
+            bail!("PASSWORD_MIN_LENGTH must be between 1 and {MAX_LENGTH}.");
+        }
+
+        let breached = match std::env::var("PASSWORD_BREACHED_LIST") {
+            Ok(path) if !path.trim().is_empty() => load_list(path.trim())?,
+            _ => HashSet::new(),
+        };
+        tracing::info!(
+            entries = breached.len(),
+            min_length,
+            "loaded password policy"
+        );
+
+        Ok(Self {
+            params,
+            min_length,
+            breached,
+        })
+    }
+
+    /// Hashes `password` on the blocking pool, since Argon2 would otherwise
+    /// stall the async workers for the whole hash.
+    pub async fn hash(&self, password: &str) -> Result<String, password_hash::Error> {
+        let params = self.params.clone();
+        let password = password.to_owned();
+        blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+            let hash = argon(params).hash_password(password.as_bytes(), &salt)?;
+            Ok(hash.to_string())
+        })
+        .await
+    }
+
+    /// Checks `password` against `stored` on the blocking pool, using the
+    /// parameters recorded in the hash rather than the configured ones.
+    pub async fn verify(&self, password: &str, stored: &str) -> Result<bool, password_hash::Error> {
+        let params = self.params.clone();
+        let password = password.to_owned();
+        let stored = stored.to_owned();
+        blocking(move || {
+            let parsed = PasswordHash::new(&stored)?;
+            Ok(argon(params)
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok())
+        })
+        .await
+    }
+
+    /// Whether `stored` was hashed with another algorithm or with any cost
+    /// below the configured one.
+    pub fn needs_rehash(&self, stored: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(stored) else {
+            return false;
+        };
+        if parsed.algorithm != ARGON2ID_IDENT || parsed.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+        match Params::try_from(&parsed) {
+            Ok(current) => {
+                current.m_cost() < self.params.m_cost()
+                    || current.t_cost() < self.params.t_cost()
+                    || current.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Rejects passwords that are too short or too long, appear in the
+    /// breached-password list, or are built from the username.
+    pub fn check(&self, password: &str, username: &str) -> Result<(), ApiError> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(ApiError::BadRequest(format!(
+                "password must be at least {} characters",
+                self.min_length
+            )));
+        }
+        if length > MAX_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "password must be at most {MAX_LENGTH} characters"
+            )));
+        }
+        if self.breached.contains(&password.to_lowercase()) {
+            return Err(ApiError::BadRequest(
+                "password appears in a list of breached passwords".to_string(),
+            ));
+        }
+        if resembles(password, username) {
+            return Err(ApiError::BadRequest(
+                "password is too similar to the username".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn argon(params: Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Runs `f` on the blocking pool, passing on a panic inside it.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Reads one password per line; blank lines and `#` comments are skipped.
+fn load_list(path: &str) -> Result<HashSet<String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read PASSWORD_BREACHED_LIST {path}"))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+/// Compares letters and digits only, case-insensitively, so `Alice.2024`
+/// counts as containing `alice`.
+fn resembles(password: &str, username: &str) -> bool {
+    fn normalize(s: &str) -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    }
+
+    let password = normalize(password);
+    let username = normalize(username);
+    if username.len() < 3 || password.is_empty() {
+        return false;
+    }
+    let reversed: String = username.chars().rev().collect();
+
+    password.contains(&username) || password.contains(&reversed) || username.contains(&password)
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use lapin::{
+    Connection, ConnectionProperties, Consumer,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Replies sent back by user-svc while provisioning a registered user.
+#[derive(Debug, Deserialize)]
+struct AuthEvent {
+    event_type: String,
+    user_id: Uuid,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Consumes the compensation side of the registration saga: when user-svc
+/// gives up on creating a profile, the auth user is removed again.
+pub fn spawn_provisioning_consumer(url: String, queue: String, pool: PgPool) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match run_consumer(&url, &queue, pool.clone()).await {
+                Ok(_) => info!("provisioning consumer exited"),
+                Err(err) => error!(?err, "provisioning consumer error"),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn run_consumer(url: &str, queue: &str, pool: PgPool) -> Result<(), anyhow::Error> {
+    let conn = Connection::connect(url, ConnectionProperties::default()).await?;
+    let channel = conn.create_channel().await?;
+    channel.basic_qos(20, BasicQosOptions::default()).await?;
+
+    channel
+        .queue_declare(
+            queue,
+            QueueDeclareOptions {
+                durable: true,
+                auto_delete: false,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            queue,
+            "auth-provisioning",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    process_events(&mut consumer, pool).await;
+
+    Ok(())
+}
+
+async fn process_events(consumer: &mut Consumer, pool: PgPool) {
+    while let Some(delivery) = consumer.next().await {
+        match delivery {
+            Ok(delivery) => {
+                let result = async {
+                    let event: AuthEvent = serde_json::from_slice(&delivery.data)?;
+                    match event.event_type.as_str() {
+                        "USER_PROVISIONING_FAILED" => {
+                            warn!(
+                                user_id = %event.user_id,
+                                reason = event.reason.as_deref().unwrap_or("unknown"),
+                                "profile provisioning failed; removing auth user"
+                            );
+                            sqlx::query("DELETE FROM users WHERE id = $1")
+                                .bind(event.user_id)
+                                .execute(&pool)
+                                .await?;
+                        }
+                        other => {
+                            warn!(event_type = other, "unhandled auth event");
+                        }
+                    }
+
+                    Ok::<(), anyhow::Error>(())
+                }
+                .await;
+
+                match result {
+                    Ok(_) => {
+                        if let Err(err) = delivery.ack(BasicAckOptions::default()).await {
+                            error!(?err, "failed to ack auth event");
+                        }
+                    }
+                    Err(err) => {
+                        error!(?err, "failed to process auth event");
+                        if let Err(nack_err) = delivery
+                            .nack(BasicNackOptions {
+                                requeue: true,
+                                ..Default::default()
+                            })
+                            .await
+                        {
+                            error!(?nack_err, "failed to nack auth event");
+                        }
+                    }
+                }
+            }
+            Err(err) => error!(?err, "provisioning consumer delivery error"),
+        }
+    }
+}
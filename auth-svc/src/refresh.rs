@@ -0,0 +1,166 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgConnection, PgPool};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    errors::ApiError,
+    secrets::{generate_token, hash_token},
+    user::UserRole,
+};
+
+pub struct RotatedToken {
+    pub user_id: Uuid,
+    pub role: UserRole,
+    /// The family, which is also the session the token belongs to.
+    pub family_id: Uuid,
+    pub refresh_token: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredToken {
+    id: Uuid,
+    user_id: Uuid,
+    family_id: Uuid,
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+    role: UserRole,
+}
+
+/// Creates a new refresh token in `family_id` and returns the opaque value
+/// handed to the client. Only its SHA-256 digest is persisted.
+pub async fn issue(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    family_id: Uuid,
+    ttl_seconds: i64,
+) -> Result<(Uuid, String), sqlx::Error> {
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(ttl_seconds);
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(family_id)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok((id, token))
+}
+
+/// Exchanges a refresh token for its successor in the same family.
+///
+/// A token that was already used, revoked or has expired revokes the whole
+/// family, so a stolen token stops working for both the thief and the owner.
+pub async fn rotate(
+    pool: &PgPool,
+    token: &str,
+    ttl_seconds: i64,
+) -> Result<RotatedToken, ApiError> {
+    let mut tx = pool.begin().await.map_err(|e| {
+        tracing::error!(?e, "failed to start refresh transaction");
+        ApiError::Internal
+    })?;
+
+    let stored = sqlx::query_as::<_, StoredToken>(
+        r#"
+        SELECT rt.id, rt.user_id, rt.family_id, rt.expires_at, rt.used_at, rt.revoked_at, u.role
+        FROM refresh_tokens rt
+        JOIN users u ON u.id = rt.user_id
+        WHERE rt.token_hash = $1 AND u.disabled_at IS NULL
+        FOR UPDATE OF rt
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!(?e, "db error on refresh");
+        ApiError::Internal
+    })?
+    .ok_or(ApiError::BadCredentials)?;
+
+    if stored.used_at.is_some() || stored.revoked_at.is_some() || stored.expires_at <= Utc::now() {
+        warn!(
+            user_id = %stored.user_id,
+            family_id = %stored.family_id,
+            "refresh token reused or expired; revoking family"
+        );
+        revoke_family(&mut tx, stored.family_id)
+            .await
+            .map_err(|_| ApiError::Internal)?;
+        tx.commit().await.map_err(|_| ApiError::Internal)?;
+        return Err(ApiError::BadCredentials);
+    }
+
+    let (next_id, refresh_token) = issue(&mut tx, stored.user_id, stored.family_id, ttl_seconds)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "failed to issue refresh token");
+            ApiError::Internal
+        })?;
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW(), replaced_by = $2 WHERE id = $1")
+        .bind(stored.id)
+        .bind(next_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok(RotatedToken {
+        user_id: stored.user_id,
+        role: stored.role,
+        family_id: stored.family_id,
+        refresh_token,
+    })
+}
+
+/// Revokes every token of the family and ends the session it belongs to.
+pub async fn revoke_family(conn: &mut PgConnection, family_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(family_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL")
+        .bind(family_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Revokes the family `token` belongs to, if the token is known and was
+/// issued to `user_id`. Tokens of other users are ignored.
+pub async fn revoke_family_of(
+    pool: &PgPool,
+    user_id: Uuid,
+    token: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let family_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT family_id FROM refresh_tokens WHERE token_hash = $1 AND user_id = $2",
+    )
+    .bind(hash_token(token))
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(family_id) = family_id {
+        revoke_family(&mut tx, family_id).await?;
+    }
+
+    tx.commit().await
+}
@@ -0,0 +1,113 @@
+use chrono::DateTime;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{jwt::Claims, user::UserRole};
+
+/// Returns true when the access token was logged out individually, belongs
+/// to a revoked session, was issued before an admin revoked every session of
+/// its user, or belongs to a deleted account or service client.
+///
+/// `iat` only has whole seconds, so the revoke-all cutoff is compared at
+/// second precision too: a token issued in the same second as the cutoff,
+/// such as one from logging straight back in, stays valid.
+pub async fn is_revoked(
+    pool: &PgPool,
+    user_id: Uuid,
+    claims: &Claims,
+) -> Result<bool, sqlx::Error> {
+    if claims.role == UserRole::Service {
+        return sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1)
+                OR NOT EXISTS (SELECT 1 FROM service_clients WHERE id = $2)
+            "#,
+        )
+        .bind(&claims.jti)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await;
+    }
+
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1)
+            OR EXISTS (
+                SELECT 1 FROM sessions WHERE id = $4 AND revoked_at IS NOT NULL
+            )
+            OR EXISTS (
+                SELECT 1 FROM user_token_revocations
+                WHERE user_id = $2
+                  AND date_trunc('second', revoked_before) > to_timestamp($3)
+            )
+            OR NOT EXISTS (SELECT 1 FROM users WHERE id = $2)
+        "#,
+    )
+    .bind(&claims.jti)
+    .bind(user_id)
+    .bind(claims.iat as f64)
+    .bind(claims.session_id())
+    .fetch_one(pool)
+    .await
+}
+
+/// Adds a single access token to the denylist until it would have expired
+/// anyway. Entries past their expiry are pruned on the way.
+pub async fn revoke_token(
+    pool: &PgPool,
+    claims: &Claims,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let expires_at = DateTime::from_timestamp(claims.exp, 0).unwrap_or_default();
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < NOW()")
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO revoked_tokens (jti, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (jti) DO NOTHING
+        "#,
+    )
+    .bind(&claims.jti)
+    .bind(user_id)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Invalidates every access and refresh token issued to `user_id` so far.
+pub async fn revoke_all_for_user(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_token_revocations (user_id, revoked_before)
+        VALUES ($1, NOW())
+        ON CONFLICT (user_id) DO UPDATE SET revoked_before = EXCLUDED.revoked_before
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
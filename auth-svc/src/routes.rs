@@ -2,18 +2,60 @@ use std::sync::Arc;
 
 use axum::{
     Router,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 
 use crate::{
     AppState,
-    handlers::{self, login, register},
+    handlers::{
+        self, activate_totp, change_password, change_role, complete_mfa_login, create_api_key,
+        create_client, delete_account, delete_api_key, delete_client, delete_user, disable_totp,
+        disable_user, enable_user, enroll_totp, impersonate_user, issue_client_token,
+        issue_password_reset, list_api_keys, list_clients, list_lockouts, list_sessions,
+        list_users, login, logout, query_audit_log, refresh, register, reset_password,
+        revoke_session, revoke_user_sessions, totp_status, unlock_user,
+    },
 };
 
 pub fn create_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/health", get(handlers::health_check))
+        .route("/.well-known/jwks.json", get(handlers::jwks))
         .route("/register", post(register))
         .route("/login", post(login))
+        .route("/login/mfa", post(complete_mfa_login))
+        .route("/refresh", post(refresh))
+        .route("/token", post(issue_client_token))
+        .route("/logout", post(logout))
+        .route("/password", post(change_password))
+        .route("/account", delete(delete_account))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/{id}", delete(revoke_session))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/{id}", delete(delete_api_key))
+        .route("/mfa/totp", get(totp_status).delete(disable_totp))
+        .route("/mfa/totp/enroll", post(enroll_totp))
+        .route("/mfa/totp/activate", post(activate_totp))
+        .route("/password/reset", post(reset_password))
+        .route("/admin/users", get(list_users))
+        .route("/admin/users/{id}", delete(delete_user))
+        .route("/admin/users/{id}/role", put(change_role))
+        .route("/admin/users/{id}/disable", post(disable_user))
+        .route("/admin/users/{id}/enable", post(enable_user))
+        .route("/admin/users/{id}/unlock", post(unlock_user))
+        .route("/admin/lockouts", get(list_lockouts))
+        .route("/admin/audit", get(query_audit_log))
+        .route("/admin/clients", get(list_clients).post(create_client))
+        .route("/admin/clients/{client_id}", delete(delete_client))
+        .route(
+            "/admin/users/{id}/revoke-sessions",
+            post(revoke_user_sessions),
+        )
+        .route(
+            "/admin/users/{id}/password-reset",
+            post(issue_password_reset),
+        )
+        .route("/admin/users/{id}/impersonate", post(impersonate_user))
         .route("/verify", get(handlers::verify_token))
+        .route("/introspect", post(handlers::introspect))
 }
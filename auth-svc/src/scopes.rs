@@ -0,0 +1,57 @@
+//! Permission scopes understood by the downstream services. Tokens carry
+//! them in the `scope` claim and `/verify` forwards them as `X-Scopes`.
+
+use crate::user::UserRole;
+
+/// Read the caller's own profile.
+pub const USERS_READ: &str = "users:read";
+/// Create and update the caller's own profile.
+pub const USERS_WRITE: &str = "users:write";
+/// Read and manage every account and profile.
+pub const USERS_ADMIN: &str = "users:admin";
+/// Read the caller's own devices.
+pub const DEVICES_READ: &str = "devices:read";
+/// Read every device.
+pub const DEVICES_READ_ANY: &str = "devices:read:any";
+/// Update the caller's own devices.
+pub const DEVICES_WRITE: &str = "devices:write";
+/// Create, reassign and delete any device.
+pub const DEVICES_ADMIN: &str = "devices:admin";
+/// Read consumption of the caller's own devices.
+pub const CONSUMPTION_READ: &str = "consumption:read";
+/// Read consumption of every device.
+pub const CONSUMPTION_READ_ANY: &str = "consumption:read:any";
+
+pub const ALL: &[&str] = &[
+    USERS_READ,
+    USERS_WRITE,
+    USERS_ADMIN,
+    DEVICES_READ,
+    DEVICES_READ_ANY,
+    DEVICES_WRITE,
+    DEVICES_ADMIN,
+    CONSUMPTION_READ,
+    CONSUMPTION_READ_ANY,
+];
+
+const CLIENT: &[&str] = &[
+    USERS_READ,
+    USERS_WRITE,
+    DEVICES_READ,
+    DEVICES_WRITE,
+    CONSUMPTION_READ,
+];
+
+/// Scopes granted to user tokens of `role`. Service clients get theirs from
+/// `service_clients` instead.
+pub fn for_role(role: UserRole) -> &'static [&'static str] {
+    match role {
+        UserRole::Admin => ALL,
+        UserRole::Client => CLIENT,
+        UserRole::Service => &[],
+    }
+}
+
+pub fn is_known(scope: &str) -> bool {
+    ALL.contains(&scope)
+}
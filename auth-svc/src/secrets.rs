@@ -0,0 +1,15 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Generates a 256-bit random token, hex encoded.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Digest stored in place of opaque tokens so a database dump cannot be
+/// replayed.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
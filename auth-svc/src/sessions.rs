@@ -0,0 +1,132 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{refresh, throttle};
+
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Where a login came from, recorded with its session.
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: String,
+}
+
+impl ClientInfo {
+    pub fn from_request(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[IpAddr]) -> Self {
+        let user_agent = headers
+            .get(axum::http::header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(MAX_USER_AGENT_LEN).collect());
+
+        Self {
+            user_agent,
+            ip: throttle::client_ip(headers, peer, trusted_proxies),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    /// Whether this is the session of the token making the request.
+    #[sqlx(skip)]
+    pub current: bool,
+}
+
+/// Records a new session and opens its refresh token family. Returns the
+/// session id and the first refresh token.
+pub async fn start(
+    pool: &PgPool,
+    user_id: Uuid,
+    client: &ClientInfo,
+    refresh_ttl_seconds: i64,
+) -> Result<(Uuid, String), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let session_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO sessions (id, user_id, user_agent, ip)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(client.user_agent.as_deref())
+    .bind(&client.ip)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let (_, refresh_token) =
+        refresh::issue(&mut tx, user_id, session_id, refresh_ttl_seconds).await?;
+
+    tx.commit().await?;
+    Ok((session_id, refresh_token))
+}
+
+/// Sessions of `user_id` that can still be refreshed, most recent first.
+pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        r#"
+        SELECT s.id, s.user_agent, s.ip, s.created_at, s.last_used_at
+        FROM sessions s
+        WHERE s.user_id = $1
+          AND s.revoked_at IS NULL
+          AND EXISTS (
+              SELECT 1 FROM refresh_tokens rt
+              WHERE rt.family_id = s.id
+                AND rt.used_at IS NULL
+                AND rt.revoked_at IS NULL
+                AND rt.expires_at > NOW()
+          )
+        ORDER BY s.last_used_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Revokes one session of `user_id` together with its refresh tokens.
+/// Access tokens issued for it stop passing `/verify`.
+pub async fn revoke(pool: &PgPool, user_id: Uuid, session_id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let owned = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM sessions WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL)",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if owned {
+        refresh::revoke_family(&mut tx, session_id).await?;
+    }
+
+    tx.commit().await?;
+    Ok(owned)
+}
+
+/// Records activity on a session, at most once a minute.
+pub async fn touch(pool: &PgPool, session_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE sessions SET last_used_at = NOW()
+        WHERE id = $1 AND last_used_at < NOW() - INTERVAL '1 minute'
+        "#,
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
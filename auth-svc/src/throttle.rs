@@ -0,0 +1,205 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::ThrottleConfig;
+
+const USER_SCOPE: &str = "user";
+const IP_SCOPE: &str = "ip";
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Lockout {
+    pub id: Uuid,
+    pub scope: String,
+    pub key: String,
+    pub failures: i32,
+    pub locked_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+}
+
+/// Source address of the request. Entries in `X-Forwarded-For` are appended
+/// by each proxy, so only the right end can be believed: the last hop is the
+/// address Traefik accepted the connection from. With `trusted_proxies` set,
+/// the header counts only when the peer is one of them, and trusted hops are
+/// skipped from the right.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[IpAddr]) -> String {
+    if !trusted_proxies.is_empty() && !trusted_proxies.contains(&peer.ip()) {
+        return peer.ip().to_string();
+    }
+
+    let hops: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|hop| hop.trim().parse().ok())
+        .collect();
+
+    hops.iter()
+        .rev()
+        .find(|hop| !trusted_proxies.contains(hop))
+        .or(hops.first())
+        .copied()
+        .unwrap_or(peer.ip())
+        .to_string()
+}
+
+/// Seconds until both the username and the source address may try again, or
+/// `None` when neither is locked out.
+pub async fn locked_for(
+    pool: &PgPool,
+    username: &str,
+    ip: &str,
+) -> Result<Option<u64>, sqlx::Error> {
+    let remaining = sqlx::query_scalar::<_, Option<f64>>(
+        r#"
+        SELECT EXTRACT(EPOCH FROM MAX(locked_until) - NOW())::DOUBLE PRECISION
+        FROM login_throttle
+        WHERE ((scope = $1 AND key = $2) OR (scope = $3 AND key = $4))
+          AND locked_until > NOW()
+        "#,
+    )
+    .bind(USER_SCOPE)
+    .bind(username)
+    .bind(IP_SCOPE)
+    .bind(ip)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(remaining.map(|secs| secs.ceil().max(1.0) as u64))
+}
+
+/// Counts a failed login against the username and the source address and
+/// locks out whichever crossed its threshold. Each further failure doubles
+/// the lockout, up to the configured maximum.
+pub async fn record_failure(
+    pool: &PgPool,
+    cfg: &ThrottleConfig,
+    username: &str,
+    ip: &str,
+) -> Result<(), sqlx::Error> {
+    record(pool, cfg, USER_SCOPE, username, cfg.max_failures_per_user).await?;
+    record(pool, cfg, IP_SCOPE, ip, cfg.max_failures_per_ip).await
+}
+
+pub async fn record_success(pool: &PgPool, username: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_throttle WHERE scope = $1 AND key = $2")
+        .bind(USER_SCOPE)
+        .bind(username)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn active_lockouts(pool: &PgPool) -> Result<Vec<Lockout>, sqlx::Error> {
+    sqlx::query_as::<_, Lockout>(
+        r#"
+        SELECT id, scope, key, failures, locked_at, locked_until
+        FROM login_lockouts
+        WHERE unlocked_at IS NULL AND locked_until > NOW()
+        ORDER BY locked_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Lifts any lockout on `username` and forgets its failed attempts.
+pub async fn unlock_user(pool: &PgPool, username: &str, admin_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM login_throttle WHERE scope = $1 AND key = $2")
+        .bind(USER_SCOPE)
+        .bind(username)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE login_lockouts
+        SET unlocked_at = NOW(), unlocked_by = $3
+        WHERE scope = $1 AND key = $2 AND unlocked_at IS NULL
+        "#,
+    )
+    .bind(USER_SCOPE)
+    .bind(username)
+    .bind(admin_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+async fn record(
+    pool: &PgPool,
+    cfg: &ThrottleConfig,
+    scope: &str,
+    key: &str,
+    threshold: i32,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let failures = sqlx::query_scalar::<_, i32>(
+        r#"
+        INSERT INTO login_throttle (scope, key, failures, last_failure_at)
+        VALUES ($1, $2, 1, NOW())
+        ON CONFLICT (scope, key) DO UPDATE
+        SET failures = CASE
+                WHEN login_throttle.last_failure_at < NOW() - make_interval(secs => $3)
+                    AND COALESCE(login_throttle.locked_until, '-infinity')
+                        < NOW() - make_interval(secs => $3)
+                    THEN 1
+                ELSE login_throttle.failures + 1
+            END,
+            last_failure_at = NOW()
+        RETURNING failures
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(cfg.failure_window_seconds as f64)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if failures >= threshold {
+        let exponent = (failures - threshold).min(20) as u32;
+        let lockout = cfg
+            .base_lockout_seconds
+            .saturating_mul(1_i64 << exponent)
+            .min(cfg.max_lockout_seconds);
+
+        sqlx::query(
+            r#"
+            UPDATE login_throttle
+            SET locked_until = NOW() + make_interval(secs => $3)
+            WHERE scope = $1 AND key = $2
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(lockout as f64)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO login_lockouts (scope, key, failures, locked_until)
+            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(failures)
+        .bind(lockout as f64)
+        .execute(&mut *tx)
+        .await?;
+
+        tracing::warn!(scope, key, failures, lockout, "login locked out");
+    }
+
+    tx.commit().await
+}
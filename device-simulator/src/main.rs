@@ -2,14 +2,16 @@ use std::{fs, path::PathBuf, time::Duration};
 
 use anyhow::Context;
 use chrono::{DateTime, Timelike, Utc};
+use hmac::{Hmac, Mac};
 use lapin::{
     BasicProperties, Channel, Connection, ConnectionProperties,
     options::{BasicPublishOptions, ConfirmSelectOptions, QueueDeclareOptions},
-    types::FieldTable,
+    types::{AMQPValue, FieldTable},
 };
 use rand::Rng;
 use serde::Deserialize;
 use serde_json::json;
+use sha2::Sha256;
 use tokio::{signal, time::interval};
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -27,6 +29,14 @@ struct SimulatorConfig {
 #[derive(Debug, Deserialize)]
 struct DeviceConfig {
     device_id: Uuid,
+    /// Secret returned by device-svc when the device was provisioned. When
+    /// absent it is derived from `DEVICE_INGESTION_KEY`, like device-svc does.
+    #[serde(default)]
+    ingestion_secret: Option<String>,
+    /// Credential generation the derived secret belongs to. It goes up each
+    /// time the device's credential is rotated.
+    #[serde(default = "default_credential_generation")]
+    credential_generation: i32,
     #[serde(default = "default_base_load")]
     night_load: f64,
     #[serde(default = "default_peak_load")]
@@ -37,6 +47,10 @@ fn default_interval_seconds() -> u64 {
     600
 }
 
+fn default_credential_generation() -> i32 {
+    1
+}
+
 fn default_base_load() -> f64 {
     0.1
 }
@@ -61,6 +75,7 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cfg = load_config(config_path()).context("failed to load simulator config")?;
+    let secrets = resolve_secrets(&cfg)?;
 
     let connection = Connection::connect(&cfg.broker_url, ConnectionProperties::default()).await?;
     let channel = connection.create_channel().await?;
@@ -92,7 +107,7 @@ async fn main() -> anyhow::Result<()> {
     loop {
         tokio::select! {
             _ = ticker.tick() => {
-                if let Err(err) = publish_batch(&channel, &cfg).await {
+                if let Err(err) = publish_batch(&channel, &cfg, &secrets).await {
                     error!(?err, "failed to publish batch");
                 }
             }
@@ -114,10 +129,43 @@ fn load_config(path: PathBuf) -> anyhow::Result<SimulatorConfig> {
     Ok(config)
 }
 
-async fn publish_batch(channel: &Channel, cfg: &SimulatorConfig) -> anyhow::Result<()> {
+/// Secret per configured device, in the same order as `cfg.devices`.
+fn resolve_secrets(cfg: &SimulatorConfig) -> anyhow::Result<Vec<String>> {
+    let master = std::env::var("DEVICE_INGESTION_KEY").ok();
+    cfg.devices
+        .iter()
+        .map(|device| match (&device.ingestion_secret, &master) {
+            (Some(secret), _) => Ok(secret.clone()),
+            (None, Some(master)) => Ok(derive_secret(
+                master,
+                device.device_id,
+                device.credential_generation,
+            )),
+            (None, None) => anyhow::bail!(
+                "device {} has no ingestion_secret and DEVICE_INGESTION_KEY is not set",
+                device.device_id
+            ),
+        })
+        .collect()
+}
+
+/// Same derivation as device-svc: hex HMAC-SHA256 of `{device_id}:{generation}`
+/// keyed with the shared master key.
+fn derive_secret(master: &str, device_id: Uuid, generation: i32) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(master.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{device_id}:{generation}").as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+async fn publish_batch(
+    channel: &Channel,
+    cfg: &SimulatorConfig,
+    secrets: &[String],
+) -> anyhow::Result<()> {
     let mut rng = rand::rng();
     let now = Utc::now();
-    for device in &cfg.devices {
+    for (device, secret) in cfg.devices.iter().zip(secrets) {
         let value = generate_measurement(device, now, &mut rng);
         let payload = json!({
             "timestamp": now,
@@ -125,13 +173,22 @@ async fn publish_batch(channel: &Channel, cfg: &SimulatorConfig) -> anyhow::Resu
             "measurement_value": value
         });
         let body = serde_json::to_vec(&payload)?;
+
+        let mut headers = FieldTable::default();
+        headers.insert(
+            "x-signature".into(),
+            AMQPValue::LongString(sign(secret, &body).into()),
+        );
+
         channel
             .basic_publish(
                 "",
                 &cfg.queue,
                 BasicPublishOptions::default(),
                 &body,
-                BasicProperties::default().with_delivery_mode(2),
+                BasicProperties::default()
+                    .with_delivery_mode(2)
+                    .with_headers(headers),
             )
             .await?
             .await?;
@@ -139,6 +196,15 @@ async fn publish_batch(channel: &Channel, cfg: &SimulatorConfig) -> anyhow::Resu
     Ok(())
 }
 
+/// Hex HMAC-SHA256 of `body`, which monitor-svc checks against the device's
+/// current credential.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
 fn generate_measurement(
     device: &DeviceConfig,
     ts: DateTime<Utc>,
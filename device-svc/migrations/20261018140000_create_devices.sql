@@ -0,0 +1,14 @@
+-- Baseline schema for devices. Databases set up by hand before this
+-- migration existed already have the table, hence IF NOT EXISTS; the later
+-- migrations bring those up to the same shape.
+CREATE TABLE IF NOT EXISTS devices (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    max_consumption INTEGER NOT NULL,
+    -- NULL once the owning account was deleted under the `unassign` policy.
+    user_id UUID,
+    credential_generation INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_devices_user ON devices (user_id);
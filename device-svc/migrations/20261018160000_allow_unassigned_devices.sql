@@ -0,0 +1,2 @@
+-- Devices of deleted accounts may be kept without an owner.
+ALTER TABLE IF EXISTS devices ALTER COLUMN user_id DROP NOT NULL;
@@ -0,0 +1,3 @@
+-- Generation of the ingestion credential; bumping it invalidates the old one.
+ALTER TABLE IF EXISTS devices
+    ADD COLUMN IF NOT EXISTS credential_generation INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,28 @@
+-- Replica of the households kept by user-svc, fed by HOUSEHOLD_* events.
+-- `version` orders snapshots; a deleted household stays as a tombstone so a
+-- late snapshot cannot bring it back.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'household_role') THEN
+        CREATE TYPE household_role AS ENUM ('OWNER', 'MEMBER', 'VIEWER');
+    END IF;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS households (
+    id UUID PRIMARY KEY,
+    version BIGINT NOT NULL,
+    deleted BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS household_members (
+    household_id UUID NOT NULL REFERENCES households(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL,
+    role household_role NOT NULL,
+    PRIMARY KEY (household_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_household_members_user ON household_members (user_id);
+
+ALTER TABLE IF EXISTS devices ADD COLUMN IF NOT EXISTS household_id UUID;
+CREATE INDEX IF NOT EXISTS idx_devices_household ON devices (household_id);
@@ -0,0 +1,245 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use lapin::{
+    Connection, ConnectionProperties, Consumer,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+};
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{AppState, households, messaging, models::Device};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Account events from auth-svc carry `user_id`, household events from
+/// user-svc carry a payload instead.
+#[derive(Debug, Deserialize)]
+struct DeviceEvent {
+    event_type: String,
+    #[serde(default)]
+    user_id: Option<Uuid>,
+    #[serde(default)]
+    payload: Option<serde_json::Value>,
+}
+
+/// What happens to the devices of a deleted account.
+#[derive(Debug, Clone, Copy)]
+pub enum DeletionPolicy {
+    /// Devices and, through monitor-svc, their consumption history are removed.
+    Delete,
+    /// Devices are kept without an owner so they can be reassigned.
+    Unassign,
+}
+
+impl DeletionPolicy {
+    pub fn from_env() -> Self {
+        match std::env::var("DEVICE_DELETION_POLICY")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "unassign" => DeletionPolicy::Unassign,
+            _ => DeletionPolicy::Delete,
+        }
+    }
+}
+
+/// Consumes account events from auth-svc, applying `policy` to the devices
+/// of deleted users, and household events from user-svc.
+pub fn spawn_user_events_consumer(
+    state: Arc<AppState>,
+    policy: DeletionPolicy,
+    url: String,
+    queue: String,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match run_consumer(&state, policy, &url, &queue).await {
+                Ok(_) => info!("device events consumer exited"),
+                Err(err) => error!(?err, "device events consumer error"),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn run_consumer(
+    state: &AppState,
+    policy: DeletionPolicy,
+    url: &str,
+    queue: &str,
+) -> Result<(), anyhow::Error> {
+    let conn = Connection::connect(url, ConnectionProperties::default()).await?;
+    let channel = conn.create_channel().await?;
+    channel.basic_qos(20, BasicQosOptions::default()).await?;
+
+    channel
+        .queue_declare(
+            queue,
+            QueueDeclareOptions {
+                durable: true,
+                auto_delete: false,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            queue,
+            "device-events",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    process_events(&mut consumer, state, policy).await;
+
+    Ok(())
+}
+
+async fn process_events(consumer: &mut Consumer, state: &AppState, policy: DeletionPolicy) {
+    while let Some(delivery) = consumer.next().await {
+        match delivery {
+            Ok(delivery) => {
+                let result = async {
+                    let event: DeviceEvent = serde_json::from_slice(&delivery.data)?;
+                    match event.event_type.as_str() {
+                        "USER_DELETED" => {
+                            let user_id = event
+                                .user_id
+                                .ok_or_else(|| anyhow::anyhow!("USER_DELETED without user_id"))?;
+                            let affected = release_devices(state, user_id, policy).await?;
+                            info!(
+                                %user_id,
+                                ?policy,
+                                affected,
+                                "released devices of deleted user"
+                            );
+                        }
+                        "HOUSEHOLD_UPDATED" => {
+                            let household = household_payload(event.payload)?;
+                            let mut tx = state.db_pool.begin().await?;
+                            let applied = households::apply_snapshot(&mut tx, &household).await?;
+                            tx.commit().await?;
+                            info!(
+                                household_id = %household.id,
+                                version = household.version,
+                                applied,
+                                "synced household members"
+                            );
+                        }
+                        "HOUSEHOLD_DELETED" => {
+                            let household = household_payload(event.payload)?;
+                            let mut tx = state.db_pool.begin().await?;
+                            let detached =
+                                households::apply_deleted(&mut tx, state, &household).await?;
+                            tx.commit().await?;
+                            info!(
+                                household_id = %household.id,
+                                ?detached,
+                                "removed deleted household"
+                            );
+                        }
+                        other => {
+                            warn!(event_type = other, "unhandled device event");
+                        }
+                    }
+
+                    Ok::<(), anyhow::Error>(())
+                }
+                .await;
+
+                match result {
+                    Ok(_) => {
+                        if let Err(err) = delivery.ack(BasicAckOptions::default()).await {
+                            error!(?err, "failed to ack device event");
+                        }
+                    }
+                    Err(err) => {
+                        error!(?err, "failed to process device event");
+                        if let Err(nack_err) = delivery
+                            .nack(BasicNackOptions {
+                                requeue: true,
+                                ..Default::default()
+                            })
+                            .await
+                        {
+                            error!(?nack_err, "failed to nack device event");
+                        }
+                    }
+                }
+            }
+            Err(err) => error!(?err, "device events consumer delivery error"),
+        }
+    }
+}
+
+fn household_payload(
+    payload: Option<serde_json::Value>,
+) -> anyhow::Result<household_replica::HouseholdPayload> {
+    let payload = payload.ok_or_else(|| anyhow::anyhow!("household event without payload"))?;
+    Ok(serde_json::from_value(payload)?)
+}
+
+/// Deletes or unassigns every device of `user_id` and records the matching
+/// sync events, so monitor-svc follows. Returns the number of devices touched.
+async fn release_devices(
+    state: &AppState,
+    user_id: Uuid,
+    policy: DeletionPolicy,
+) -> Result<usize, sqlx::Error> {
+    let mut tx = state.db_pool.begin().await?;
+
+    let affected = match policy {
+        DeletionPolicy::Delete => {
+            let ids = sqlx::query_scalar::<_, Uuid>(
+                "DELETE FROM devices WHERE user_id = $1 RETURNING id",
+            )
+            .bind(user_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for id in &ids {
+                outbox::enqueue(&mut tx, &state.sync_queue, &messaging::device_deleted(*id))
+                    .await?;
+            }
+            ids.len()
+        }
+        DeletionPolicy::Unassign => {
+            let devices = sqlx::query_as::<_, Device>(
+                r#"
+                UPDATE devices
+                SET user_id = NULL
+                WHERE user_id = $1
+                RETURNING id, name, max_consumption, user_id, household_id, credential_generation, created_at
+                "#,
+            )
+            .bind(user_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for device in &devices {
+                outbox::enqueue(
+                    &mut tx,
+                    &state.sync_queue,
+                    &messaging::device_event("DEVICE_UPDATED", device),
+                )
+                .await?;
+            }
+            devices.len()
+        }
+    };
+
+    tx.commit().await?;
+    Ok(affected)
+}
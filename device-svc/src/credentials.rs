@@ -0,0 +1,28 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Derives the per-device secrets telemetry producers sign measurements
+/// with. monitor-svc holds the same master key, so secrets never travel
+/// between the services.
+pub struct IngestionKeys {
+    master: Vec<u8>,
+}
+
+impl IngestionKeys {
+    pub fn from_env() -> Self {
+        let master = std::env::var("DEVICE_INGESTION_KEY")
+            .expect("DEVICE_INGESTION_KEY must be set for device-svc.");
+        Self {
+            master: master.into_bytes(),
+        }
+    }
+
+    /// Hex-encoded secret of `generation` of the device's credential.
+    pub fn device_secret(&self, device_id: Uuid, generation: i32) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.master).expect("HMAC accepts any key length");
+        mac.update(format!("{device_id}:{generation}").as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
@@ -10,6 +10,7 @@ use serde_json::json;
 #[derive(Debug)]
 pub enum ApiError {
     Unauthorized(String),
+    Forbidden(String),
     Conflict,
     BadRequest(String),
     NotFound(String),
@@ -20,6 +21,7 @@ impl Display for ApiError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             ApiError::Unauthorized(err) => write!(f, "unauthorized: {}", err),
+            ApiError::Forbidden(err) => write!(f, "forbidden: {}", err),
             ApiError::Conflict => write!(f, "conflict"),
             ApiError::BadRequest(err) => write!(f, "bad request: {}", err),
             ApiError::Internal => write!(f, "internal server error"),
@@ -35,6 +37,10 @@ impl IntoResponse for ApiError {
                 (StatusCode::UNAUTHORIZED, Json(json!({ "error": msg }))).into_response()
             }
 
+            ApiError::Forbidden(msg) => {
+                (StatusCode::FORBIDDEN, Json(json!({ "error": msg }))).into_response()
+            }
+
             ApiError::Conflict => {
                 (StatusCode::CONFLICT, Json(json!({ "error": "conflict" }))).into_response()
             }
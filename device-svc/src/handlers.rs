@@ -6,13 +6,17 @@ use axum::{
     http::StatusCode,
     response::IntoResponse,
 };
+use serde::Serialize;
+use sqlx::PgConnection;
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     AppState,
     errors::ApiError,
-    models::{CreateRequest, Device, UpdateRequest, UserRole},
+    households, messaging,
+    models::{CreateRequest, Device, ProvisionedDevice, UpdateRequest},
+    scopes,
 };
 
 pub async fn health_check() -> impl IntoResponse {
@@ -31,39 +35,100 @@ pub async fn debug_headers(headers: axum::http::HeaderMap) -> String {
 #[axum::debug_handler]
 pub async fn create(
     State(state): State<Arc<AppState>>,
-    user: AuthenticatedUser,
     Json(payload): Json<CreateRequest>,
-) -> Result<Json<Device>, ApiError> {
+) -> Result<Json<ProvisionedDevice>, ApiError> {
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    if let Some(household_id) = payload.household_id {
+        require_household(&mut tx, household_id).await?;
+    }
+
     let device = sqlx::query_as::<_, Device>(
         r#"
-          INSERT INTO devices (name, max_consumption, user_id)
-          VALUES ($1, $2, $3)
-          RETURNING id, name, max_consumption, user_id, created_at  
+          INSERT INTO devices (name, max_consumption, user_id, household_id)
+          VALUES ($1, $2, $3, $4)
+          RETURNING id, name, max_consumption, user_id, household_id, credential_generation, created_at
         "#,
     )
     .bind(&payload.name)
-    .bind(&payload.max_consumption)
-    .bind(&payload.user_id)
-    .fetch_one(&state.db_pool)
+    .bind(payload.max_consumption)
+    .bind(payload.user_id)
+    .bind(payload.household_id)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
-        if let sqlx::Error::Database(db) = &e {
-            if db.code().as_deref() == Some("23505") {
-                return ApiError::Conflict;
-            }
+        if let sqlx::Error::Database(db) = &e
+            && db.code().as_deref() == Some("23505")
+        {
+            return ApiError::Conflict;
         }
         ApiError::Internal
     })?;
 
-    if let Err(err) = state
-        .publisher
-        .publish_device_event("DEVICE_CREATED", &device)
+    enqueue_event(
+        &mut tx,
+        &state,
+        &messaging::device_event("DEVICE_CREATED", &device),
+    )
+    .await?;
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    let ingestion_secret = state
+        .ingestion_keys
+        .device_secret(device.id, device.credential_generation);
+
+    Ok(Json(ProvisionedDevice {
+        device,
+        ingestion_secret,
+    }))
+}
+
+/// Issues a new ingestion secret for a device. Measurements signed with the
+/// previous one are quarantined by monitor-svc from then on.
+pub async fn rotate_credential(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ProvisionedDevice>, ApiError> {
+    let mut tx = state
+        .db_pool
+        .begin()
         .await
-    {
-        error!(?err, "failed to publish DEVICE_CREATED event");
-    }
+        .map_err(|_| ApiError::Internal)?;
 
-    Ok(Json(device))
+    let device = sqlx::query_as::<_, Device>(
+        r#"
+        UPDATE devices
+        SET credential_generation = credential_generation + 1
+        WHERE id = $1
+        RETURNING id, name, max_consumption, user_id, household_id, credential_generation, created_at
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| ApiError::Internal)?
+    .ok_or(ApiError::NotFound("device id not found".to_string()))?;
+
+    enqueue_event(
+        &mut tx,
+        &state,
+        &messaging::device_event("DEVICE_UPDATED", &device),
+    )
+    .await?;
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    let ingestion_secret = state
+        .ingestion_keys
+        .device_secret(device.id, device.credential_generation);
+
+    Ok(Json(ProvisionedDevice {
+        device,
+        ingestion_secret,
+    }))
 }
 
 #[axum::debug_handler]
@@ -72,21 +137,29 @@ pub async fn get_device(
     user: AuthenticatedUser,
     Path(id): Path<Uuid>,
 ) -> Result<Json<Device>, ApiError> {
-    let query = if user.role == UserRole::ADMIN {
+    let any = user.has_scope(scopes::DEVICES_READ_ANY);
+    if !any {
+        user.require_scope(scopes::DEVICES_READ)?;
+    }
+
+    let query = if any {
         r#"
-        SELECT id, name, max_consumption, user_id, created_at
+        SELECT id, name, max_consumption, user_id, household_id, credential_generation, created_at
         FROM devices
         WHERE id = $1
         "#
     } else {
         r#"
-        SELECT id, name, max_consumption, user_id, created_at
+        SELECT id, name, max_consumption, user_id, household_id, credential_generation, created_at
         FROM devices
-        WHERE id = $1 AND user_id = $2
+        WHERE id = $1
+          AND (user_id = $2 OR household_id IN (
+              SELECT household_id FROM household_members WHERE user_id = $2
+          ))
         "#
     };
 
-    let device = if user.role == UserRole::ADMIN {
+    let device = if any {
         sqlx::query_as::<_, Device>(query)
             .bind(id)
             .fetch_optional(&state.db_pool)
@@ -101,14 +174,6 @@ pub async fn get_device(
     .map_err(|_| ApiError::Internal)?
     .ok_or(ApiError::NotFound("device id not found".to_string()))?;
 
-    if let Err(err) = state
-        .publisher
-        .publish_device_event("DEVICE_UPDATED", &device)
-        .await
-    {
-        error!(?err, "failed to publish DEVICE_UPDATED event");
-    }
-
     Ok(Json(device))
 }
 
@@ -116,10 +181,10 @@ pub async fn list_devices(
     State(state): State<Arc<AppState>>,
     user: AuthenticatedUser,
 ) -> Result<Json<Vec<Device>>, ApiError> {
-    let devices = if user.role == UserRole::ADMIN {
+    let devices = if user.has_scope(scopes::DEVICES_READ_ANY) {
         sqlx::query_as::<_, Device>(
             r#"
-            SELECT id, name, max_consumption, user_id, created_at
+            SELECT id, name, max_consumption, user_id, household_id, credential_generation, created_at
             FROM devices
             ORDER BY created_at DESC
             "#,
@@ -127,11 +192,15 @@ pub async fn list_devices(
         .fetch_all(&state.db_pool)
         .await
     } else {
+        user.require_scope(scopes::DEVICES_READ)?;
         sqlx::query_as::<_, Device>(
             r#"
-            SELECT id, name, max_consumption, user_id, created_at
+            SELECT id, name, max_consumption, user_id, household_id, credential_generation, created_at
             FROM devices
             WHERE user_id = $1
+               OR household_id IN (
+                   SELECT household_id FROM household_members WHERE user_id = $1
+               )
             ORDER BY created_at DESC
             "#,
         )
@@ -144,49 +213,91 @@ pub async fn list_devices(
     Ok(Json(devices))
 }
 
+/// Updates a device the caller owns or shares as household owner or member.
+/// Moving a device between households is reserved to its owner, who must be
+/// allowed to write in the target household.
 pub async fn update(
     State(state): State<Arc<AppState>>,
     user: AuthenticatedUser,
     Json(payload): Json<UpdateRequest>,
 ) -> Result<Json<Device>, ApiError> {
-    let query = if user.role == UserRole::ADMIN {
+    let any = user.has_scope(scopes::DEVICES_ADMIN);
+    if !any {
+        user.require_scope(scopes::DEVICES_WRITE)?;
+    }
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    let current = sqlx::query_as::<_, Device>(
         r#"
-        UPDATE devices
-        SET 
-            name = COALESCE($2, name),
-            max_consumption = COALESCE($3, max_consumption)
+        SELECT id, name, max_consumption, user_id, household_id, credential_generation, created_at
+        FROM devices
         WHERE id = $1
-        RETURNING id, name, max_consumption, user_id, created_at
-        "#
-    } else {
+        FOR UPDATE
+        "#,
+    )
+    .bind(payload.id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| ApiError::Internal)?
+    .ok_or(ApiError::NotFound("device id not found".to_string()))?;
+
+    if !any && !can_write(&mut tx, &current, user.user_id).await? {
+        return Err(ApiError::NotFound("device id not found".to_string()));
+    }
+
+    if let Some(target) = payload.household_id {
+        if !any && current.user_id != Some(user.user_id) {
+            return Err(ApiError::Forbidden(
+                "only the device owner can change its household".to_string(),
+            ));
+        }
+        if let Some(household_id) = target {
+            require_household(&mut tx, household_id).await?;
+            if !any {
+                let role = households::role_of(&mut *tx, household_id, user.user_id)
+                    .await
+                    .map_err(|_| ApiError::Internal)?;
+                if !role.is_some_and(|r| r.can_write()) {
+                    return Err(ApiError::Forbidden(
+                        "not an owner or member of the household".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    let device = sqlx::query_as::<_, Device>(
         r#"
         UPDATE devices
-        SET 
+        SET
             name = COALESCE($2, name),
-            max_consumption = COALESCE($3, max_consumption)
-        WHERE id = $1 AND user_id = $4
-        RETURNING id, name, max_consumption, user_id, created_at
-        "#
-    };
+            max_consumption = COALESCE($3, max_consumption),
+            household_id = CASE WHEN $4 THEN $5 ELSE household_id END
+        WHERE id = $1
+        RETURNING id, name, max_consumption, user_id, household_id, credential_generation, created_at
+        "#,
+    )
+    .bind(payload.id)
+    .bind(payload.name.as_ref())
+    .bind(payload.max_consumption)
+    .bind(payload.household_id.is_some())
+    .bind(payload.household_id.flatten())
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| ApiError::Internal)?;
 
-    let device = if user.role == UserRole::ADMIN {
-        sqlx::query_as::<_, Device>(query)
-            .bind(payload.id)
-            .bind(payload.name.as_ref())
-            .bind(payload.max_consumption)
-            .fetch_optional(&state.db_pool)
-            .await
-    } else {
-        sqlx::query_as::<_, Device>(query)
-            .bind(payload.id)
-            .bind(payload.name.as_ref())
-            .bind(payload.max_consumption)
-            .bind(user.user_id)
-            .fetch_optional(&state.db_pool)
-            .await
-    }
-    .map_err(|_| ApiError::Internal)?
-    .ok_or(ApiError::NotFound("device id not found".to_string()))?;
+    enqueue_event(
+        &mut tx,
+        &state,
+        &messaging::device_event("DEVICE_UPDATED", &device),
+    )
+    .await?;
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
 
     Ok(Json(device))
 }
@@ -194,12 +305,13 @@ pub async fn update(
 #[axum::debug_handler]
 pub async fn delete_device(
     State(state): State<Arc<AppState>>,
-    user: AuthenticatedUser,
     Path(id): Path<Uuid>,
 ) -> Result<StatusCode, ApiError> {
-    if user.role != UserRole::ADMIN {
-        return Err(ApiError::Unauthorized("Not an admin".to_string()));
-    }
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
 
     let result = sqlx::query(
         r#"
@@ -208,22 +320,21 @@ pub async fn delete_device(
         "#,
     )
     .bind(id)
-    .execute(&state.db_pool)
+    .execute(&mut *tx)
     .await;
 
     match result {
         Ok(r) if r.rows_affected() > 0 => {
-            if let Err(err) = state.publisher.publish_device_deleted(id).await {
-                error!(?err, "failed to publish DEVICE_DELETED event");
-            }
+            enqueue_event(&mut tx, &state, &messaging::device_deleted(id)).await?;
+            tx.commit().await.map_err(|_| ApiError::Internal)?;
             Ok(StatusCode::NO_CONTENT)
         }
         Ok(_) => Err(ApiError::NotFound("device id not found".to_string())),
         Err(e) => {
-            if let sqlx::Error::Database(db) = &e {
-                if db.code().as_deref() == Some("23503") {
-                    return Err(ApiError::Conflict);
-                }
+            if let sqlx::Error::Database(db) = &e
+                && db.code().as_deref() == Some("23503")
+            {
+                return Err(ApiError::Conflict);
             }
             Err(ApiError::Internal)
         }
@@ -233,48 +344,103 @@ pub async fn delete_device(
 #[axum::debug_handler]
 pub async fn delete_all_devices(
     State(state): State<Arc<AppState>>,
-    user: AuthenticatedUser,
 ) -> Result<StatusCode, ApiError> {
-    if user.role != UserRole::ADMIN {
-        return Err(ApiError::Unauthorized("Not an admin".to_string()));
-    }
-
-    let device_ids = sqlx::query_scalar::<_, Uuid>("SELECT id FROM devices")
-        .fetch_all(&state.db_pool)
+    let mut tx = state
+        .db_pool
+        .begin()
         .await
         .map_err(|_| ApiError::Internal)?;
 
-    let result = sqlx::query(
+    let result = sqlx::query_scalar::<_, Uuid>(
         r#"
         DELETE FROM devices
+        RETURNING id
         "#,
     )
-    .execute(&state.db_pool)
+    .fetch_all(&mut *tx)
     .await;
 
     match result {
-        Ok(_) => {
+        Ok(device_ids) => {
             for id in device_ids {
-                if let Err(err) = state.publisher.publish_device_deleted(id).await {
-                    error!(?err, "failed to publish DEVICE_DELETED event");
-                }
+                enqueue_event(&mut tx, &state, &messaging::device_deleted(id)).await?;
             }
+            tx.commit().await.map_err(|_| ApiError::Internal)?;
             Ok(StatusCode::NO_CONTENT)
         }
         Err(e) => {
-            if let sqlx::Error::Database(db) = &e {
-                if db.code().as_deref() == Some("23503") {
-                    return Err(ApiError::Conflict);
-                }
+            if let sqlx::Error::Database(db) = &e
+                && db.code().as_deref() == Some("23503")
+            {
+                return Err(ApiError::Conflict);
             }
             Err(ApiError::Internal)
         }
     }
 }
 
+/// Whether `user_id` owns the device or is an owner or member of its
+/// household.
+async fn can_write(
+    conn: &mut PgConnection,
+    device: &Device,
+    user_id: Uuid,
+) -> Result<bool, ApiError> {
+    if device.user_id == Some(user_id) {
+        return Ok(true);
+    }
+    let Some(household_id) = device.household_id else {
+        return Ok(false);
+    };
+    let role = households::role_of(conn, household_id, user_id)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+    Ok(role.is_some_and(|r| r.can_write()))
+}
+
+async fn require_household(conn: &mut PgConnection, household_id: Uuid) -> Result<(), ApiError> {
+    if households::exists(conn, household_id)
+        .await
+        .map_err(|_| ApiError::Internal)?
+    {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest("unknown household".to_string()))
+    }
+}
+
+async fn enqueue_event<T: Serialize>(
+    conn: &mut PgConnection,
+    state: &AppState,
+    event: &T,
+) -> Result<(), ApiError> {
+    outbox::enqueue(conn, &state.sync_queue, event)
+        .await
+        .map_err(|e| {
+            error!(?e, "failed to enqueue sync event");
+            ApiError::Internal
+        })
+}
+
+/// Principal forwarded by the gateway. `scopes` come from `X-Scopes`, which
+/// auth-svc fills from the role of a user or the grant of a service client.
 pub struct AuthenticatedUser {
     pub user_id: Uuid,
-    pub role: UserRole,
+    pub scopes: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!("missing scope {scope}")))
+        }
+    }
 }
 
 impl<S> FromRequestParts<S> for AuthenticatedUser
@@ -285,7 +451,7 @@ where
 
     async fn from_request_parts(
         parts: &mut axum::http::request::Parts,
-        state: &S,
+        _state: &S,
     ) -> Result<Self, Self::Rejection> {
         let user_id_header = parts
             .headers
@@ -297,20 +463,13 @@ where
         let user_id = Uuid::parse_str(user_id_str)
             .map_err(|_| ApiError::Unauthorized("Invalid UUID in X-User-Id".to_string()))?;
 
-        let role_header = parts
+        let scopes = parts
             .headers
-            .get("x-user-role")
-            .ok_or(ApiError::Unauthorized("Missing X-User-Role".to_string()))?;
-        let role_str = role_header
-            .to_str()
-            .map_err(|_| ApiError::Unauthorized("Invalid X-User-Role header".to_string()))?;
-
-        let role = match role_str.to_uppercase().as_str() {
-            "ADMIN" => UserRole::ADMIN,
-            "CLIENT" => UserRole::CLIENT,
-            _ => return Err(ApiError::Unauthorized("Invalid role".to_string())),
-        };
+            .get("x-scopes")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
 
-        Ok(AuthenticatedUser { user_id, role })
+        Ok(AuthenticatedUser { user_id, scopes })
     }
 }
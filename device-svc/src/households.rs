@@ -0,0 +1,74 @@
+//! Local replica of user-svc households, used to authorize access to shared
+//! devices without a call to user-svc.
+
+use household_replica::HouseholdPayload;
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::{
+    AppState, messaging,
+    models::{Device, HouseholdRole},
+};
+
+pub use household_replica::apply_snapshot;
+
+pub async fn role_of<'e>(
+    executor: impl PgExecutor<'e>,
+    household_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<HouseholdRole>, sqlx::Error> {
+    sqlx::query_scalar::<_, HouseholdRole>(
+        "SELECT role FROM household_members WHERE household_id = $1 AND user_id = $2",
+    )
+    .bind(household_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn exists<'e>(
+    executor: impl PgExecutor<'e>,
+    household_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM households WHERE id = $1 AND NOT deleted)",
+    )
+    .bind(household_id)
+    .fetch_one(executor)
+    .await
+}
+
+/// Marks the household deleted and detaches its devices, recording a
+/// `DEVICE_UPDATED` for each so monitor-svc follows. Returns the number of
+/// devices detached, or `None` if the event was stale.
+pub async fn apply_deleted(
+    conn: &mut PgConnection,
+    state: &AppState,
+    household: &HouseholdPayload,
+) -> Result<Option<usize>, sqlx::Error> {
+    if !household_replica::apply_deleted(conn, household).await? {
+        return Ok(None);
+    }
+
+    let devices = sqlx::query_as::<_, Device>(
+        r#"
+        UPDATE devices
+        SET household_id = NULL
+        WHERE household_id = $1
+        RETURNING id, name, max_consumption, user_id, household_id, credential_generation, created_at
+        "#,
+    )
+    .bind(household.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for device in &devices {
+        outbox::enqueue(
+            conn,
+            &state.sync_queue,
+            &messaging::device_event("DEVICE_UPDATED", device),
+        )
+        .await?;
+    }
+    Ok(Some(devices.len()))
+}
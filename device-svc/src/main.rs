@@ -1,24 +1,31 @@
 use std::sync::Arc;
 
-use axum::{Router, routing::get};
+use axum::{Router, middleware::from_fn_with_state};
 use routes::create_routes;
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod consumers;
+mod credentials;
 mod errors;
 mod handlers;
+mod households;
 mod messaging;
-mod middleware;
 mod models;
 mod routes;
+mod scopes;
 
-use messaging::EventPublisher;
+use credentials::IngestionKeys;
+use outbox::EventPublisher;
 
 struct AppState {
     db_pool: PgPool,
-    publisher: EventPublisher,
+    sync_queue: String,
+    ingestion_keys: IngestionKeys,
 }
 
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::registry()
@@ -32,21 +39,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let broker_url =
         std::env::var("SYNC_BROKER_URL").expect("SYNC_BROKER_URL must be set for device-svc.");
     let sync_queue = std::env::var("SYNC_QUEUE").unwrap_or_else(|_| "sync.events".into());
+    let device_events_queue =
+        std::env::var("DEVICE_EVENTS_QUEUE").unwrap_or_else(|_| "device.events".into());
 
     let pool = PgPoolOptions::new()
         .max_connections(20)
         .connect(&db_url)
         .await?;
 
-    let publisher = EventPublisher::new(broker_url, sync_queue).await?;
+    MIGRATOR.run(&pool).await?;
+
+    let publisher = EventPublisher::new(broker_url.clone(), vec![sync_queue.clone()]);
+    let _relay_handle = outbox::spawn_relay(pool.clone(), publisher);
 
     let shared_state = Arc::new(AppState {
         db_pool: pool,
-        publisher,
+        sync_queue,
+        ingestion_keys: IngestionKeys::from_env(),
     });
 
+    let _user_events_handle = consumers::spawn_user_events_consumer(
+        shared_state.clone(),
+        consumers::DeletionPolicy::from_env(),
+        broker_url,
+        device_events_queue,
+    );
+
     let app = Router::new()
         .merge(create_routes())
+        .layer(from_fn_with_state(
+            impersonation::read_only_from_env(),
+            impersonation::guard,
+        ))
         .with_state(shared_state.clone());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
@@ -7,15 +7,31 @@ pub struct Device {
     pub id: Uuid,
     pub name: String,
     pub max_consumption: i32,
-    pub user_id: Uuid,
+    /// `None` once the owning account was deleted under the `unassign` policy.
+    pub user_id: Option<Uuid>,
+    /// Household sharing the device; its members read it, and owners and
+    /// members also control it.
+    pub household_id: Option<Uuid>,
+    pub credential_generation: i32,
     pub created_at: DateTime<Utc>,
 }
 
+/// Returned when a credential is issued; the secret is not stored and cannot
+/// be shown again, only rotated.
+#[derive(Debug, Serialize)]
+pub struct ProvisionedDevice {
+    #[serde(flatten)]
+    pub device: Device,
+    pub ingestion_secret: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateRequest {
     pub user_id: Uuid,
     pub name: String,
     pub max_consumption: i32,
+    #[serde(default)]
+    pub household_id: Option<Uuid>,
 }
 
 #[derive(Deserialize)]
@@ -23,10 +39,18 @@ pub struct UpdateRequest {
     pub id: Uuid,
     pub name: Option<String>,
     pub max_consumption: Option<f64>,
+    /// Absent leaves the household unchanged, `null` detaches the device.
+    #[serde(default, deserialize_with = "present")]
+    pub household_id: Option<Option<Uuid>>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum UserRole {
-    CLIENT,
-    ADMIN,
+pub use household_replica::HouseholdRole;
+
+/// Tells an explicit `null` apart from a missing field.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
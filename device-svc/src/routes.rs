@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use axum::{
     Router,
-    middleware::from_fn,
+    middleware::from_fn_with_state,
     routing::{delete, get, post, put},
 };
 
@@ -10,22 +10,28 @@ use crate::{
     AppState,
     handlers::{
         self, create, debug_headers, delete_all_devices, delete_device, get_device, health_check,
-        update,
+        rotate_credential, update,
     },
-    middleware::require_admin_middleware,
+    scopes,
 };
 
 pub fn create_routes() -> Router<Arc<AppState>> {
+    let admin = || from_fn_with_state(scopes::DEVICES_ADMIN, scopes::require);
+
     Router::new()
         .route("/health", get(health_check))
-        .route(
-            "/create",
-            post(create).route_layer(from_fn(require_admin_middleware)),
-        )
+        .route("/create", post(create).route_layer(admin()))
         .route("/read/all", get(handlers::list_devices))
         .route("/debug", get(debug_headers))
         .route("/read/{id}", get(get_device))
         .route("/update", put(update))
-        .route("/delete/{id}", delete(delete_device))
-        .route("/delete/all", delete(delete_all_devices))
+        .route(
+            "/credential/{id}",
+            post(rotate_credential).route_layer(admin()),
+        )
+        .route("/delete/{id}", delete(delete_device).route_layer(admin()))
+        .route(
+            "/delete/all",
+            delete(delete_all_devices).route_layer(admin()),
+        )
 }
@@ -0,0 +1,30 @@
+//! Permission scopes forwarded by auth-svc in `X-Scopes`.
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{errors::ApiError, handlers::AuthenticatedUser};
+
+/// Read the caller's own devices and those of its households.
+pub const DEVICES_READ: &str = "devices:read";
+/// Read every device.
+pub const DEVICES_READ_ANY: &str = "devices:read:any";
+/// Update the caller's own devices and those of households it owns or is a member of.
+pub const DEVICES_WRITE: &str = "devices:write";
+/// Create, update and delete any device.
+pub const DEVICES_ADMIN: &str = "devices:admin";
+
+/// Route layer rejecting principals without the scope it is built with:
+/// `route_layer(from_fn_with_state(scopes::DEVICES_ADMIN, scopes::require))`.
+pub async fn require(
+    State(scope): State<&'static str>,
+    user: AuthenticatedUser,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    user.require_scope(scope)?;
+    Ok(next.run(req).await)
+}
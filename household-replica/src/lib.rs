@@ -0,0 +1,115 @@
+//! Replica of the households kept by user-svc, shared by device-svc and
+//! monitor-svc. Both keep the same `households` and `household_members`
+//! tables, fed by `HOUSEHOLD_UPDATED` and `HOUSEHOLD_DELETED` snapshots.
+//! `version` orders the snapshots; a deleted household stays as a tombstone
+//! so a late snapshot cannot bring it back.
+
+use serde::Deserialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Role of a user within a household, as replicated from user-svc.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "UPPERCASE")]
+#[sqlx(type_name = "household_role", rename_all = "UPPERCASE")]
+pub enum HouseholdRole {
+    Owner,
+    Member,
+    Viewer,
+}
+
+impl HouseholdRole {
+    /// Whether the role may change the household's devices.
+    pub fn can_write(self) -> bool {
+        matches!(self, HouseholdRole::Owner | HouseholdRole::Member)
+    }
+}
+
+/// Body of `HOUSEHOLD_UPDATED` and `HOUSEHOLD_DELETED`: the full membership
+/// at `version`.
+#[derive(Debug, Deserialize)]
+pub struct HouseholdPayload {
+    pub id: Uuid,
+    pub version: i64,
+    #[serde(default)]
+    pub members: Vec<MemberPayload>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberPayload {
+    pub user_id: Uuid,
+    pub role: HouseholdRole,
+}
+
+/// Replaces the membership of the household with the snapshot. Returns
+/// `false` if the snapshot was stale and ignored.
+pub async fn apply_snapshot(
+    conn: &mut PgConnection,
+    household: &HouseholdPayload,
+) -> Result<bool, sqlx::Error> {
+    if !advance(conn, household.id, household.version, false).await? {
+        return Ok(false);
+    }
+
+    clear_members(conn, household.id).await?;
+    for member in &household.members {
+        sqlx::query(
+            "INSERT INTO household_members (household_id, user_id, role) VALUES ($1, $2, $3)",
+        )
+        .bind(household.id)
+        .bind(member.user_id)
+        .bind(member.role)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(true)
+}
+
+/// Leaves a tombstone for the household and drops its members. Returns
+/// `false` if the event was stale and ignored.
+pub async fn apply_deleted(
+    conn: &mut PgConnection,
+    household: &HouseholdPayload,
+) -> Result<bool, sqlx::Error> {
+    if !advance(conn, household.id, household.version, true).await? {
+        return Ok(false);
+    }
+
+    clear_members(conn, household.id).await?;
+    Ok(true)
+}
+
+/// Records `version` for the household unless a newer one is already held.
+/// Returns whether the snapshot is current and should be applied.
+async fn advance(
+    conn: &mut PgConnection,
+    household_id: Uuid,
+    version: i64,
+    deleted: bool,
+) -> Result<bool, sqlx::Error> {
+    let applied = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO households (id, version, deleted)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (id) DO UPDATE
+        SET version = EXCLUDED.version, deleted = EXCLUDED.deleted
+        WHERE households.version < EXCLUDED.version
+        RETURNING id
+        "#,
+    )
+    .bind(household_id)
+    .bind(version)
+    .bind(deleted)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(applied.is_some())
+}
+
+async fn clear_members(conn: &mut PgConnection, household_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM household_members WHERE household_id = $1")
+        .bind(household_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
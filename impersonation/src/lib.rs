@@ -0,0 +1,58 @@
+//! Requests made with an admin's impersonation token, shared by user-svc and
+//! device-svc. auth-svc forwards the admin's id in `X-Impersonated-By` next
+//! to the impersonated `X-User-Id`.
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+pub const IMPERSONATED_BY_HEADER: &str = "x-impersonated-by";
+
+/// `IMPERSONATION_READ_ONLY` (default `true`) rejects every request that is
+/// not a GET, HEAD or OPTIONS while impersonating.
+pub fn read_only_from_env() -> bool {
+    std::env::var("IMPERSONATION_READ_ONLY")
+        .map(|v| !matches!(v.trim().to_lowercase().as_str(), "false" | "0" | "no"))
+        .unwrap_or(true)
+}
+
+/// Logs impersonated requests and, in read-only mode, refuses changes with
+/// the services' usual `403` body:
+/// `layer(from_fn_with_state(read_only, impersonation::guard))`.
+pub async fn guard(State(read_only): State<bool>, req: Request, next: Next) -> Response {
+    if let Err(msg) = check(read_only, &req) {
+        return (StatusCode::FORBIDDEN, Json(json!({ "error": msg }))).into_response();
+    }
+    next.run(req).await
+}
+
+fn check(read_only: bool, req: &Request) -> Result<(), &'static str> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    let admin_id = header(IMPERSONATED_BY_HEADER);
+    if admin_id.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!(
+        admin_id,
+        user_id = header("x-user-id"),
+        method = %req.method(),
+        path = req.uri().path(),
+        "impersonated request"
+    );
+    if read_only && !req.method().is_safe() {
+        return Err("changes are not allowed while impersonating");
+    }
+    Ok(())
+}
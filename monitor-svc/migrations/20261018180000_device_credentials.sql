@@ -0,0 +1,14 @@
+-- Generation of the device's ingestion credential, mirrored from device-svc.
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS credential_generation INTEGER;
+
+-- Measurements that failed authentication, kept for inspection.
+CREATE TABLE IF NOT EXISTS quarantined_measurements (
+    id BIGSERIAL PRIMARY KEY,
+    device_id UUID,
+    payload TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_quarantined_measurements_received_at
+    ON quarantined_measurements (received_at);
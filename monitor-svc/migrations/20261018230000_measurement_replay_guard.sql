@@ -0,0 +1,8 @@
+-- device-svc gave every device that predates ingestion credentials
+-- generation 1 (its column default); mirror that for rows synced before the
+-- generation was part of the device events.
+UPDATE devices SET credential_generation = 1 WHERE credential_generation IS NULL;
+
+-- Timestamp of the newest accepted measurement per device. A measurement
+-- must be newer to count, so a captured signed message cannot be replayed.
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS last_measurement_at TIMESTAMPTZ;
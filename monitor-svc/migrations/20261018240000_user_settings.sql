@@ -0,0 +1,10 @@
+-- Regional and billing settings of each user, mirrored from the
+-- USER_CREATED and USER_UPDATED events of user-svc. Consumption days are
+-- bucketed in the device owner's timezone.
+CREATE TABLE IF NOT EXISTS user_settings (
+    user_id UUID PRIMARY KEY,
+    timezone TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    tariff_ref TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,67 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+};
+use uuid::Uuid;
+
+/// Read consumption of the caller's own devices and those of its households.
+pub const CONSUMPTION_READ: &str = "consumption:read";
+/// Read consumption of every device.
+pub const CONSUMPTION_READ_ANY: &str = "consumption:read:any";
+
+/// Principal forwarded by the gateway in `X-User-Id` and `X-Scopes`.
+pub struct Principal {
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+    /// Admin acting as `user_id`, from `X-Impersonated-By`.
+    pub impersonated_by: Option<Uuid>,
+}
+
+impl Principal {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn require_scope(&self, scope: &str) -> Result<(), StatusCode> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Sync + Send,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_id = parts
+            .headers
+            .get("x-user-id")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| Uuid::parse_str(v).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let scopes = parts
+            .headers
+            .get("x-scopes")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+
+        let impersonated_by = parts
+            .headers
+            .get("x-impersonated-by")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| Uuid::parse_str(v).ok());
+
+        Ok(Principal {
+            user_id,
+            scopes,
+            impersonated_by,
+        })
+    }
+}
@@ -7,6 +7,8 @@ pub struct AppConfig {
     pub data_broker_url: String,
     pub sync_queue: String,
     pub measurement_queue: String,
+    pub ingestion_key: String,
+    pub quarantine_retention_days: i64,
     pub http_host: String,
     pub http_port: u16,
     pub max_db_connections: u32,
@@ -24,6 +26,13 @@ impl AppConfig {
             sync_queue: env::var("SYNC_QUEUE").unwrap_or_else(|_| "sync.events".into()),
             measurement_queue: env::var("MEASUREMENT_QUEUE")
                 .unwrap_or_else(|_| "device.measurements".into()),
+            ingestion_key: env::var("DEVICE_INGESTION_KEY")
+                .expect("DEVICE_INGESTION_KEY must be set for monitor-svc"),
+            quarantine_retention_days: env::var("QUARANTINE_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|days| *days > 0)
+                .unwrap_or(30),
             http_host: env::var("SERVICE_HOST").unwrap_or_else(|_| "0.0.0.0".into()),
             http_port: env::var("SERVICE_PORT")
                 .ok()
@@ -1,22 +1,25 @@
 use std::{sync::Arc, time::Duration};
 
+use chrono::Utc;
 use futures_util::StreamExt;
 use lapin::{
     Channel, Connection, ConnectionProperties, Consumer,
+    message::Delivery,
     options::{
         BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions,
         QueueDeclareOptions,
     },
-    types::FieldTable,
+    types::{AMQPValue, FieldTable},
 };
 use sqlx::{self, PgPool};
 use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
 use crate::{
     config::AppConfig,
-    db::{self, HourBucket},
-    models::{MeasurementMessage, SyncEnvelope},
+    db, ingestion,
+    models::{DevicePayload, MeasurementMessage, SyncEnvelope, UserPayload},
 };
 
 const RECONNECT_DELAY: Duration = Duration::from_secs(5);
@@ -65,7 +68,7 @@ async fn run_measurement_consumer(cfg: Arc<AppConfig>, pool: PgPool) -> Result<(
         )
         .await?;
 
-    process_measurements(&mut consumer, pool).await;
+    process_measurements(&mut consumer, cfg.ingestion_key.as_bytes(), pool).await;
 
     Ok(())
 }
@@ -106,38 +109,49 @@ async fn declare_queue(channel: &Channel, name: &str) -> Result<(), lapin::Error
     Ok(())
 }
 
-async fn process_measurements(consumer: &mut Consumer, pool: PgPool) {
+/// Outcome of checking a measurement against its device's credential.
+enum Verdict {
+    Accepted(MeasurementMessage),
+    Quarantined {
+        device_id: Option<Uuid>,
+        reason: &'static str,
+    },
+}
+
+async fn process_measurements(consumer: &mut Consumer, master_key: &[u8], pool: PgPool) {
     while let Some(delivery) = consumer.next().await {
         match delivery {
             Ok(delivery) => {
                 let result: Result<(), anyhow::Error> = async {
-                    let msg: MeasurementMessage = serde_json::from_slice(&delivery.data)?;
-                    let bucket = HourBucket::from_timestamp(msg.timestamp);
-
-                    match db::accumulate_measurement(
-                        &pool,
-                        msg.device_id,
-                        &bucket,
-                        msg.measurement_value,
-                    )
-                    .await
-                    {
-                        Ok(_) => Ok(()),
-                        Err(sqlx::Error::Database(db_err))
-                            if db_err.code().as_deref() == Some("23503") =>
-                        {
-                            db::ensure_device_placeholder(&pool, msg.device_id).await?;
-                            db::accumulate_measurement(
+                    let signature = signature_header(&delivery);
+                    match authenticate(&pool, master_key, &delivery.data, signature).await? {
+                        Verdict::Accepted(msg) => {
+                            let fresh = db::accumulate_measurement(
                                 &pool,
                                 msg.device_id,
-                                &bucket,
+                                msg.timestamp,
                                 msg.measurement_value,
                             )
                             .await?;
-                            Ok(())
+                            if !fresh {
+                                let reason = "replayed measurement";
+                                warn!(device_id = %msg.device_id, reason, "quarantined measurement");
+                                db::quarantine_measurement(
+                                    &pool,
+                                    Some(msg.device_id),
+                                    &delivery.data,
+                                    reason,
+                                )
+                                .await?;
+                            }
+                        }
+                        Verdict::Quarantined { device_id, reason } => {
+                            warn!(?device_id, reason, "quarantined measurement");
+                            db::quarantine_measurement(&pool, device_id, &delivery.data, reason)
+                                .await?;
                         }
-                        Err(err) => Err(err.into()),
                     }
+                    Ok(())
                 }
                 .await;
 
@@ -166,6 +180,60 @@ async fn process_measurements(consumer: &mut Consumer, pool: PgPool) {
     }
 }
 
+/// Accepts a measurement only when it names a known device, is signed with
+/// that device's current credential and is not dated in the future. Whether
+/// it is newer than the last accepted one is checked when it is stored.
+async fn authenticate(
+    pool: &PgPool,
+    master_key: &[u8],
+    body: &[u8],
+    signature: Option<&str>,
+) -> Result<Verdict, sqlx::Error> {
+    let Ok(msg) = serde_json::from_slice::<MeasurementMessage>(body) else {
+        return Ok(Verdict::Quarantined {
+            device_id: None,
+            reason: "malformed message",
+        });
+    };
+
+    let quarantine = |reason| Verdict::Quarantined {
+        device_id: Some(msg.device_id),
+        reason,
+    };
+
+    let Some(signature) = signature else {
+        return Ok(quarantine("missing signature"));
+    };
+
+    let generation = match db::credential_generation(pool, msg.device_id).await? {
+        None => return Ok(quarantine("unknown device")),
+        Some(None) => return Ok(quarantine("device has no credential")),
+        Some(Some(generation)) => generation,
+    };
+
+    if !ingestion::verify_signature(master_key, msg.device_id, generation, body, signature) {
+        return Ok(quarantine("invalid signature"));
+    }
+
+    if msg.timestamp > Utc::now() + ingestion::MAX_CLOCK_SKEW {
+        return Ok(quarantine("timestamp in the future"));
+    }
+
+    Ok(Verdict::Accepted(msg))
+}
+
+fn signature_header(delivery: &Delivery) -> Option<&str> {
+    let headers = delivery.properties.headers().as_ref()?;
+    headers
+        .inner()
+        .iter()
+        .find(|(key, _)| key.as_str() == ingestion::SIGNATURE_HEADER)
+        .and_then(|(_, value)| match value {
+            AMQPValue::LongString(value) => std::str::from_utf8(value.as_bytes()).ok(),
+            _ => None,
+        })
+}
+
 async fn process_sync_events(consumer: &mut Consumer, pool: PgPool) {
     while let Some(delivery) = consumer.next().await {
         match delivery {
@@ -175,6 +243,7 @@ async fn process_sync_events(consumer: &mut Consumer, pool: PgPool) {
                     match event.event_type.as_str() {
                         "DEVICE_CREATED" | "DEVICE_UPDATED" => {
                             if let Some(payload) = event.payload {
+                                let payload: DevicePayload = serde_json::from_value(payload)?;
                                 db::upsert_device(&pool, &payload).await?;
                             } else {
                                 warn!("device event missing payload: {:?}", event);
@@ -184,11 +253,47 @@ async fn process_sync_events(consumer: &mut Consumer, pool: PgPool) {
                             if let Some(device_id) = event.device_id {
                                 db::delete_device(&pool, device_id).await?;
                             } else if let Some(payload) = event.payload {
+                                let payload: DevicePayload = serde_json::from_value(payload)?;
                                 db::delete_device(&pool, payload.id).await?;
                             } else {
                                 warn!("device delete event missing id: {:?}", event);
                             }
                         }
+                        "HOUSEHOLD_UPDATED" | "HOUSEHOLD_DELETED" => {
+                            if let Some(payload) = event.payload {
+                                let household: household_replica::HouseholdPayload =
+                                    serde_json::from_value(payload)?;
+                                let deleted = event.event_type == "HOUSEHOLD_DELETED";
+                                let applied =
+                                    db::apply_household(&pool, &household, deleted).await?;
+                                info!(
+                                    household_id = %household.id,
+                                    version = household.version,
+                                    deleted,
+                                    applied,
+                                    "synced household"
+                                );
+                            } else {
+                                warn!("household event missing payload: {:?}", event);
+                            }
+                        }
+                        "USER_CREATED" | "USER_UPDATED" => {
+                            if let Some(payload) = event.payload {
+                                let user: UserPayload = serde_json::from_value(payload)?;
+                                if user.timezone.is_some() && user.currency.is_some() {
+                                    db::upsert_user_settings(&pool, &user).await?;
+                                }
+                            } else {
+                                warn!("user event missing payload: {:?}", event);
+                            }
+                        }
+                        "USER_DELETED" => {
+                            if let Some(user_id) = event.user_id {
+                                db::delete_user_settings(&pool, user_id).await?;
+                            } else {
+                                warn!("user delete event missing id: {:?}", event);
+                            }
+                        }
                         other => {
                             warn!(event_type = other, "unhandled sync event");
                         }
@@ -2,25 +2,31 @@ use chrono::{DateTime, NaiveDate, Timelike, Utc};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
-use crate::models::{DevicePayload, HourlyPoint};
+use household_replica::HouseholdPayload;
+
+use crate::models::{DevicePayload, UserPayload, UserSettings};
 
 pub async fn upsert_device(pool: &PgPool, payload: &DevicePayload) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
-        INSERT INTO devices (id, user_id, name, max_consumption, metadata, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, NOW(), NOW())
+        INSERT INTO devices (id, user_id, household_id, name, max_consumption, credential_generation, metadata, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), NOW())
         ON CONFLICT (id) DO UPDATE
         SET user_id = EXCLUDED.user_id,
+            household_id = EXCLUDED.household_id,
             name = EXCLUDED.name,
             max_consumption = EXCLUDED.max_consumption,
+            credential_generation = EXCLUDED.credential_generation,
             metadata = EXCLUDED.metadata,
             updated_at = NOW()
         "#,
     )
     .bind(payload.id)
     .bind(payload.user_id)
+    .bind(payload.household_id)
     .bind(&payload.name)
     .bind(payload.max_consumption)
+    .bind(payload.credential_generation)
     .bind(&payload.metadata)
     .execute(pool)
     .await?;
@@ -36,21 +42,88 @@ pub async fn delete_device(pool: &PgPool, device_id: Uuid) -> Result<(), sqlx::E
     Ok(())
 }
 
-pub async fn ensure_device_placeholder(pool: &PgPool, device_id: Uuid) -> Result<(), sqlx::Error> {
+/// Whether `user_id` owns `device_id` or belongs to the household it is
+/// shared with. Unknown devices are not readable.
+pub async fn can_read(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM devices d
+            WHERE d.id = $1
+              AND (d.user_id = $2 OR EXISTS (
+                  SELECT 1 FROM household_members m
+                  WHERE m.household_id = d.household_id AND m.user_id = $2
+              ))
+        )
+        "#,
+    )
+    .bind(device_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Replaces the household's members with the snapshot, or marks the
+/// household deleted when `deleted`. Snapshots older than the one held are
+/// ignored; returns whether this one was applied.
+pub async fn apply_household(
+    pool: &PgPool,
+    household: &HouseholdPayload,
+    deleted: bool,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let applied = if deleted {
+        household_replica::apply_deleted(&mut tx, household).await?
+    } else {
+        household_replica::apply_snapshot(&mut tx, household).await?
+    };
+    tx.commit().await?;
+    Ok(applied)
+}
+
+/// Current credential generation of `device_id`. `None` when the device is
+/// unknown; `Some(None)` when it predates ingestion credentials.
+pub async fn credential_generation(
+    pool: &PgPool,
+    device_id: Uuid,
+) -> Result<Option<Option<i32>>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<i32>>("SELECT credential_generation FROM devices WHERE id = $1")
+        .bind(device_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn quarantine_measurement(
+    pool: &PgPool,
+    device_id: Option<Uuid>,
+    payload: &[u8],
+    reason: &str,
+) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
-        INSERT INTO devices (id, name, created_at, updated_at)
-        VALUES ($1, $2, NOW(), NOW())
-        ON CONFLICT (id) DO NOTHING
+        INSERT INTO quarantined_measurements (device_id, payload, reason)
+        VALUES ($1, $2, $3)
         "#,
     )
     .bind(device_id)
-    .bind("Unknown device")
+    .bind(String::from_utf8_lossy(payload).as_ref())
+    .bind(reason)
     .execute(pool)
     .await?;
     Ok(())
 }
 
+/// Deletes quarantined measurements older than `retention_days`.
+pub async fn prune_quarantine(pool: &PgPool, retention_days: i64) -> Result<u64, sqlx::Error> {
+    let deleted = sqlx::query(
+        "DELETE FROM quarantined_measurements WHERE received_at < NOW() - make_interval(days => $1)",
+    )
+    .bind(retention_days as i32)
+    .execute(pool)
+    .await?;
+    Ok(deleted.rows_affected())
+}
+
 pub struct HourBucket {
     pub day: NaiveDate,
     pub hour: i32,
@@ -66,12 +139,33 @@ impl HourBucket {
     }
 }
 
+/// Adds a measurement to its hourly bucket, unless it is not newer than the
+/// last one accepted for the device. Returns `false` for such replays.
 pub async fn accumulate_measurement(
     pool: &PgPool,
     device_id: Uuid,
-    bucket: &HourBucket,
+    timestamp: DateTime<Utc>,
     value: f64,
-) -> Result<(), sqlx::Error> {
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let fresh = sqlx::query(
+        r#"
+        UPDATE devices SET last_measurement_at = $2
+        WHERE id = $1 AND (last_measurement_at IS NULL OR last_measurement_at < $2)
+        "#,
+    )
+    .bind(device_id)
+    .bind(timestamp)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        == 1;
+    if !fresh {
+        return Ok(false);
+    }
+
+    let bucket = HourBucket::from_timestamp(timestamp);
     sqlx::query(
         r#"
         INSERT INTO hourly_consumption (device_id, day, hour, value, updated_at)
@@ -85,34 +179,96 @@ pub async fn accumulate_measurement(
     .bind(bucket.day)
     .bind(bucket.hour)
     .bind(value)
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
-    Ok(())
+
+    tx.commit().await?;
+    Ok(true)
 }
 
+/// Hourly buckets of the device starting in `[from, to)`, with the UTC
+/// start of each hour.
 pub async fn fetch_consumption(
     pool: &PgPool,
     device_id: Uuid,
-    day: NaiveDate,
-) -> Result<Vec<HourlyPoint>, sqlx::Error> {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<(DateTime<Utc>, f64)>, sqlx::Error> {
     let rows = sqlx::query(
         r#"
-        SELECT hour, value
+        SELECT day, hour, value
         FROM hourly_consumption
-        WHERE device_id = $1 AND day = $2
-        ORDER BY hour ASC
+        WHERE device_id = $1 AND day BETWEEN $2 AND $3
+        ORDER BY day ASC, hour ASC
         "#,
     )
     .bind(device_id)
-    .bind(day)
+    .bind(from.date_naive())
+    .bind(to.date_naive())
     .fetch_all(pool)
     .await?;
 
     Ok(rows
         .into_iter()
-        .map(|row| HourlyPoint {
-            hour: row.get::<i16, _>("hour") as i32,
-            value: row.get::<f64, _>("value"),
+        .filter_map(|row| {
+            let day = row.get::<NaiveDate, _>("day");
+            let hour = row.get::<i16, _>("hour") as u32;
+            let start = day.and_hms_opt(hour, 0, 0)?.and_utc();
+            (from <= start && start < to).then(|| (start, row.get::<f64, _>("value")))
         })
         .collect())
 }
+
+pub async fn upsert_user_settings(pool: &PgPool, payload: &UserPayload) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_settings (user_id, timezone, currency, tariff_ref, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (user_id) DO UPDATE
+        SET timezone = EXCLUDED.timezone,
+            currency = EXCLUDED.currency,
+            tariff_ref = EXCLUDED.tariff_ref,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(payload.id)
+    .bind(&payload.timezone)
+    .bind(&payload.currency)
+    .bind(&payload.tariff_ref)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_user_settings(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM user_settings WHERE user_id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Settings of the device's owner, if the device has one whose profile
+/// monitor-svc has seen.
+pub async fn owner_settings(
+    pool: &PgPool,
+    device_id: Uuid,
+) -> Result<Option<UserSettings>, sqlx::Error> {
+    let row = sqlx::query(
+        r#"
+        SELECT s.timezone, s.currency, s.tariff_ref
+        FROM devices d
+        JOIN user_settings s ON s.user_id = d.user_id
+        WHERE d.id = $1
+        "#,
+    )
+    .bind(device_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| UserSettings {
+        timezone: row.get("timezone"),
+        currency: row.get("currency"),
+        tariff_ref: row.get("tariff_ref"),
+    }))
+}
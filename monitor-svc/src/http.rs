@@ -7,12 +7,15 @@ use axum::{
     response::{IntoResponse, Response},
     routing::get,
 };
-use chrono::NaiveDate;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Offset, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
-    AppState, db,
+    AppState,
+    auth::{self, Principal},
+    db,
     models::{ConsumptionResponse, HourlyPoint},
 };
 
@@ -34,11 +37,54 @@ struct ConsumptionQuery {
 }
 
 async fn get_consumption(
+    principal: Principal,
     Query(query): Query<ConsumptionQuery>,
     state: axum::extract::State<Arc<AppState>>,
 ) -> Result<Json<ConsumptionResponse>, Response> {
+    if let Some(admin_id) = principal.impersonated_by {
+        tracing::info!(
+            %admin_id,
+            user_id = %principal.user_id,
+            device_id = %query.device_id,
+            "impersonated consumption read"
+        );
+    }
+
+    if !principal.has_scope(auth::CONSUMPTION_READ_ANY) {
+        principal
+            .require_scope(auth::CONSUMPTION_READ)
+            .map_err(IntoResponse::into_response)?;
+
+        let allowed = db::can_read(&state.db_pool, query.device_id, principal.user_id)
+            .await
+            .map_err(|err| {
+                tracing::error!(?err, "failed to check device access");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            })?;
+        if !allowed {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        }
+    }
+
     let day = NaiveDate::parse_from_str(&query.day, "%Y-%m-%d")
         .map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
+    let next_day = day
+        .succ_opt()
+        .ok_or_else(|| StatusCode::BAD_REQUEST.into_response())?;
+
+    let settings = db::owner_settings(&state.db_pool, query.device_id)
+        .await
+        .map_err(|err| {
+            tracing::error!(?err, "failed to fetch owner settings");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+    // Devices without a known owner profile are reported in UTC, and so are
+    // zones that cannot be mapped onto the stored UTC hours.
+    let tz = settings
+        .as_ref()
+        .and_then(|s| s.timezone.parse::<Tz>().ok())
+        .filter(|tz| whole_hour_offsets(day, *tz))
+        .unwrap_or(Tz::UTC);
 
     let mut points = vec![
         HourlyPoint {
@@ -51,22 +97,101 @@ async fn get_consumption(
         point.hour = idx as i32;
     }
 
-    let existing = db::fetch_consumption(&state.db_pool, query.device_id, day)
-        .await
-        .map_err(|err| {
-            tracing::error!(?err, "failed to fetch consumption");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        })?;
+    let existing = db::fetch_consumption(
+        &state.db_pool,
+        query.device_id,
+        start_of_day(day, tz),
+        start_of_day(next_day, tz),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!(?err, "failed to fetch consumption");
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
 
-    for item in existing {
-        if let Some(target) = points.get_mut(item.hour as usize) {
-            target.value = item.value;
+    // On the day clocks go back, the repeated local hour holds both.
+    for (start, value) in existing {
+        let hour = start.with_timezone(&tz).hour() as usize;
+        if let Some(target) = points.get_mut(hour) {
+            target.value += value;
         }
     }
 
+    let (currency, tariff_ref) = settings
+        .map(|s| (Some(s.currency), s.tariff_ref))
+        .unwrap_or_default();
     Ok(Json(ConsumptionResponse {
         device_id: query.device_id,
         day,
+        timezone: tz.name().to_string(),
+        currency,
+        tariff_ref,
         points,
     }))
 }
+
+/// Whether `tz` is a whole number of hours from UTC throughout `day`, so
+/// each stored UTC hour falls into exactly one local hour.
+fn whole_hour_offsets(day: NaiveDate, tz: Tz) -> bool {
+    let Some(next_day) = day.succ_opt() else {
+        return false;
+    };
+    [
+        start_of_day(day, tz),
+        start_of_day(next_day, tz) - Duration::seconds(1),
+    ]
+    .iter()
+    .all(|instant| instant.with_timezone(&tz).offset().fix().local_minus_utc() % 3600 == 0)
+}
+
+/// First instant of `day` in `tz`. Where midnight falls into a DST gap the
+/// day starts when the gap ends.
+fn start_of_day(day: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let midnight = day.and_time(NaiveTime::MIN);
+    (0..=2)
+        .find_map(|hours| {
+            tz.from_local_datetime(&(midnight + Duration::hours(hours)))
+                .earliest()
+        })
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn start_of_day_is_local_midnight() {
+        let start = start_of_day(day(2026, 7, 1), Tz::Europe__Bucharest);
+        assert_eq!(start.to_rfc3339(), "2026-06-30T21:00:00+00:00");
+    }
+
+    #[test]
+    fn start_of_day_skips_a_dst_gap_at_midnight() {
+        // Santiago moves from 00:00 to 01:00 on the first Sunday of September.
+        let start = start_of_day(day(2026, 9, 6), Tz::America__Santiago);
+        assert_eq!(start.to_rfc3339(), "2026-09-06T04:00:00+00:00");
+    }
+
+    #[test]
+    fn whole_hour_zones_are_accepted() {
+        assert!(whole_hour_offsets(day(2026, 10, 18), Tz::UTC));
+        assert!(whole_hour_offsets(day(2026, 10, 25), Tz::Europe__Bucharest));
+        assert!(whole_hour_offsets(day(2026, 3, 8), Tz::America__New_York));
+    }
+
+    #[test]
+    fn fractional_hour_zones_are_rejected() {
+        assert!(!whole_hour_offsets(day(2026, 10, 18), Tz::Asia__Kolkata));
+        assert!(!whole_hour_offsets(day(2026, 10, 18), Tz::Asia__Kathmandu));
+        assert!(!whole_hour_offsets(
+            day(2026, 1, 15),
+            Tz::Australia__Adelaide
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::db;
+
+/// AMQP header carrying the hex HMAC-SHA256 of the message body.
+pub const SIGNATURE_HEADER: &str = "x-signature";
+/// How far a measurement may be dated ahead of monitor-svc's clock.
+pub const MAX_CLOCK_SKEW: chrono::Duration = chrono::Duration::minutes(5);
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Checks that `body` was signed with the current credential of `device_id`.
+/// The device secret is derived from the master key shared with device-svc.
+pub fn verify_signature(
+    master_key: &[u8],
+    device_id: Uuid,
+    generation: i32,
+    body: &[u8],
+    signature: &str,
+) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+
+    let mut derive =
+        Hmac::<Sha256>::new_from_slice(master_key).expect("HMAC accepts any key length");
+    derive.update(format!("{device_id}:{generation}").as_bytes());
+    let device_secret = hex::encode(derive.finalize().into_bytes());
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(device_secret.as_bytes())
+        .expect("HMAC accepts any key length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Deletes quarantined measurements past their retention once an hour.
+pub fn spawn_quarantine_pruner(pool: PgPool, retention_days: i64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match db::prune_quarantine(&pool, retention_days).await {
+                Ok(0) => {}
+                Ok(deleted) => info!(deleted, "pruned quarantined measurements"),
+                Err(err) => error!(?err, "failed to prune quarantined measurements"),
+            }
+            tokio::time::sleep(PRUNE_INTERVAL).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &[u8] = b"test-master-key";
+    const BODY: &[u8] = br#"{"device_id":"x","measurement_value":0.4}"#;
+
+    /// Signs like a producer does with the secret device-svc hands out.
+    fn sign(device_id: Uuid, generation: i32, body: &[u8]) -> String {
+        let mut derive = Hmac::<Sha256>::new_from_slice(MASTER).unwrap();
+        derive.update(format!("{device_id}:{generation}").as_bytes());
+        let secret = hex::encode(derive.finalize().into_bytes());
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn accepts_the_current_generation() {
+        let device_id = Uuid::new_v4();
+        let signature = sign(device_id, 3, BODY);
+        assert!(verify_signature(MASTER, device_id, 3, BODY, &signature));
+        assert!(verify_signature(
+            MASTER,
+            device_id,
+            3,
+            BODY,
+            &signature.to_uppercase()
+        ));
+    }
+
+    #[test]
+    fn rejects_a_rotated_generation() {
+        let device_id = Uuid::new_v4();
+        let signature = sign(device_id, 2, BODY);
+        assert!(!verify_signature(MASTER, device_id, 3, BODY, &signature));
+    }
+
+    #[test]
+    fn rejects_another_device_or_master_key() {
+        let device_id = Uuid::new_v4();
+        let signature = sign(device_id, 1, BODY);
+        assert!(!verify_signature(
+            MASTER,
+            Uuid::new_v4(),
+            1,
+            BODY,
+            &signature
+        ));
+        assert!(!verify_signature(
+            b"other-key",
+            device_id,
+            1,
+            BODY,
+            &signature
+        ));
+    }
+
+    #[test]
+    fn rejects_a_modified_body() {
+        let device_id = Uuid::new_v4();
+        let signature = sign(device_id, 1, BODY);
+        let tampered = br#"{"device_id":"x","measurement_value":9.4}"#;
+        assert!(!verify_signature(
+            MASTER, device_id, 1, tampered, &signature
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let device_id = Uuid::new_v4();
+        let signature = sign(device_id, 1, BODY);
+        assert!(!verify_signature(MASTER, device_id, 1, BODY, ""));
+        assert!(!verify_signature(MASTER, device_id, 1, BODY, "not hex"));
+        assert!(!verify_signature(
+            MASTER,
+            device_id,
+            1,
+            BODY,
+            &signature[1..]
+        ));
+        assert!(!verify_signature(
+            MASTER,
+            device_id,
+            1,
+            BODY,
+            &signature[..32]
+        ));
+    }
+}
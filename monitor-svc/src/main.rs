@@ -8,10 +8,12 @@ use tokio::signal;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod auth;
 mod config;
 mod consumers;
 mod db;
 mod http;
+mod ingestion;
 mod models;
 
 static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
@@ -44,6 +46,8 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let _measurement_handle = spawn_measurement_consumer(cfg.clone(), pool.clone());
     let _sync_handle = spawn_sync_consumer(cfg.clone(), pool.clone());
+    let _prune_handle =
+        ingestion::spawn_quarantine_pruner(pool.clone(), cfg.quarantine_retention_days);
 
     let app = router(state);
 
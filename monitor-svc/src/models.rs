@@ -12,17 +12,45 @@ pub struct MeasurementMessage {
 #[derive(Debug, Deserialize)]
 pub struct SyncEnvelope {
     pub event_type: String,
+    #[serde(default)]
+    pub user_id: Option<Uuid>,
     pub device_id: Option<Uuid>,
-    pub payload: Option<DevicePayload>,
+    /// Shape depends on `event_type`.
+    pub payload: Option<serde_json::Value>,
+}
+
+/// The settings monitor-svc keeps from a `USER_CREATED` or `USER_UPDATED`
+/// profile. Profiles published before user-svc had them lack these fields.
+#[derive(Debug, Deserialize)]
+pub struct UserPayload {
+    pub id: Uuid,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub tariff_ref: Option<String>,
+}
+
+/// Settings of a device's owner that consumption is reported in.
+#[derive(Debug)]
+pub struct UserSettings {
+    pub timezone: String,
+    pub currency: String,
+    pub tariff_ref: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DevicePayload {
     pub id: Uuid,
     pub user_id: Option<Uuid>,
+    #[serde(default)]
+    pub household_id: Option<Uuid>,
     pub name: String,
     pub max_consumption: Option<i32>,
     #[serde(default)]
+    pub credential_generation: Option<i32>,
+    #[serde(default)]
     pub metadata: serde_json::Value,
 }
 
@@ -36,5 +64,11 @@ pub struct HourlyPoint {
 pub struct ConsumptionResponse {
     pub device_id: Uuid,
     pub day: NaiveDate,
+    /// Zone `day` and the hours of `points` are in: the owner's, or UTC when
+    /// the owner is unknown or their zone is not a whole number of hours from
+    /// UTC on `day`, since measurements are stored in UTC hours.
+    pub timezone: String,
+    pub currency: Option<String>,
+    pub tariff_ref: Option<String>,
     pub points: Vec<HourlyPoint>,
 }
@@ -0,0 +1,164 @@
+//! Transactional outbox shared by the services that publish events: rows are
+//! written in the same transaction as the change they describe and relayed
+//! to RabbitMQ in the background.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+mod publisher;
+
+pub use publisher::EventPublisher;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+const BATCH_SIZE: i64 = 100;
+const MAX_BACKOFF_SECONDS: i64 = 300;
+/// Advisory lock held by the relay that is currently publishing, so replicas
+/// of a service never interleave and each queue keeps its order.
+const RELAY_LOCK: i64 = 0x6f75_7462_6f78;
+
+#[derive(sqlx::FromRow)]
+struct PendingEvent {
+    id: i64,
+    queue: String,
+    payload: serde_json::Value,
+    attempts: i32,
+}
+
+/// Records `event` for `queue` in the caller's transaction. The relay
+/// publishes it after the transaction commits, so an event exists if and
+/// only if the write it describes does.
+pub async fn enqueue<T: Serialize>(
+    conn: &mut PgConnection,
+    queue: &str,
+    event: &T,
+) -> Result<(), sqlx::Error> {
+    let payload = serde_json::to_value(event).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+    sqlx::query("INSERT INTO outbox (queue, payload) VALUES ($1, $2)")
+        .bind(queue)
+        .bind(payload)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Publishes pending outbox rows in the background. Delivery is at least
+/// once: a row is only marked sent after the broker confirmed it.
+pub fn spawn_relay(pool: PgPool, publisher: EventPublisher) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_prune = Instant::now();
+        loop {
+            match relay_batch(&pool, &publisher).await {
+                Ok(sent) if sent as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(err) => error!(?err, "outbox relay failed"),
+            }
+
+            if last_prune.elapsed() >= PRUNE_INTERVAL {
+                if let Err(err) = prune(&pool).await {
+                    error!(?err, "failed to prune outbox");
+                }
+                last_prune = Instant::now();
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+}
+
+/// Publishes due events oldest first and returns how many were sent. The
+/// batch stops at the first failure, since the broker is most likely down;
+/// the failed row is retried with exponential backoff. A queue whose oldest
+/// unsent row is still backing off is skipped entirely, so consumers see the
+/// events of each queue in the order they were written.
+async fn relay_batch(pool: &PgPool, publisher: &EventPublisher) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let leader = sqlx::query_scalar::<_, bool>("SELECT pg_try_advisory_xact_lock($1)")
+        .bind(RELAY_LOCK)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !leader {
+        return Ok(0);
+    }
+
+    let events = sqlx::query_as::<_, PendingEvent>(
+        r#"
+        SELECT o.id, o.queue, o.payload, o.attempts
+        FROM outbox o
+        WHERE o.sent_at IS NULL AND o.next_attempt_at <= NOW()
+          AND NOT EXISTS (
+              SELECT 1 FROM outbox older
+              WHERE older.queue = o.queue
+                AND older.sent_at IS NULL
+                AND older.id < o.id
+                AND older.next_attempt_at > NOW()
+          )
+        ORDER BY o.id
+        LIMIT $1
+        "#,
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut sent = 0;
+    for event in events {
+        let body = event.payload.to_string();
+        match publisher.publish(&event.queue, body.as_bytes()).await {
+            Ok(()) => {
+                sqlx::query(
+                    "UPDATE outbox SET sent_at = NOW(), attempts = attempts + 1 WHERE id = $1",
+                )
+                .bind(event.id)
+                .execute(&mut *tx)
+                .await?;
+                sent += 1;
+            }
+            Err(err) => {
+                let backoff = backoff_seconds(event.attempts);
+                warn!(
+                    ?err,
+                    id = event.id,
+                    attempts = event.attempts + 1,
+                    retry_in = backoff,
+                    "failed to publish outbox event"
+                );
+                sqlx::query(
+                    r#"
+                    UPDATE outbox
+                    SET attempts = attempts + 1,
+                        last_error = $2,
+                        next_attempt_at = NOW() + make_interval(secs => $3)
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(event.id)
+                .bind(err.to_string())
+                .bind(backoff as f64)
+                .execute(&mut *tx)
+                .await?;
+                break;
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(sent)
+}
+
+fn backoff_seconds(attempts: i32) -> i64 {
+    (1i64 << attempts.clamp(0, 16)).min(MAX_BACKOFF_SECONDS)
+}
+
+async fn prune(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM outbox WHERE sent_at < NOW() - INTERVAL '7 days'")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
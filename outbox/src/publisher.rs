@@ -0,0 +1,100 @@
+use lapin::{
+    BasicProperties, Channel, Connection, ConnectionProperties,
+    options::{BasicPublishOptions, ConfirmSelectOptions, QueueDeclareOptions},
+    types::FieldTable,
+};
+use tokio::sync::Mutex;
+
+pub struct EventPublisher {
+    url: String,
+    queues: Vec<String>,
+    inner: Mutex<Option<ChannelState>>,
+}
+
+struct ChannelState {
+    #[allow(dead_code)]
+    connection: Connection,
+    channel: Channel,
+}
+
+impl EventPublisher {
+    /// Creates a publisher that connects lazily. Every queue in `queues` is
+    /// declared on connect so events are never routed to a missing queue.
+    pub fn new(url: String, queues: Vec<String>) -> Self {
+        Self {
+            url,
+            queues,
+            inner: Mutex::new(None),
+        }
+    }
+
+    /// Publishes `body` to `queue` and waits for the broker to confirm it.
+    pub async fn publish(&self, queue: &str, body: &[u8]) -> anyhow::Result<()> {
+        let channel = self.ensure_channel().await?;
+
+        let result = async {
+            let confirmation = channel
+                .basic_publish(
+                    "",
+                    queue,
+                    BasicPublishOptions::default(),
+                    body,
+                    BasicProperties::default().with_delivery_mode(2),
+                )
+                .await?
+                .await?;
+            if confirmation.is_nack() {
+                anyhow::bail!("broker rejected event for {queue}");
+            }
+            Ok(())
+        }
+        .await;
+
+        if result.is_err() {
+            self.reset().await;
+        }
+        result
+    }
+
+    async fn ensure_channel(&self) -> anyhow::Result<Channel> {
+        if let Some(state) = self.inner.lock().await.as_ref() {
+            return Ok(state.channel.clone());
+        }
+
+        self.reconnect().await
+    }
+
+    async fn reconnect(&self) -> anyhow::Result<Channel> {
+        let conn = Connection::connect(&self.url, ConnectionProperties::default()).await?;
+        let channel = conn.create_channel().await?;
+        for queue in &self.queues {
+            channel
+                .queue_declare(
+                    queue,
+                    QueueDeclareOptions {
+                        durable: true,
+                        auto_delete: false,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+
+        let mut guard = self.inner.lock().await;
+        *guard = Some(ChannelState {
+            connection: conn,
+            channel: channel.clone(),
+        });
+
+        Ok(channel)
+    }
+
+    async fn reset(&self) {
+        let mut guard = self.inner.lock().await;
+        *guard = None;
+    }
+}
@@ -0,0 +1,9 @@
+-- Regional, billing and contact settings of a profile. The defaults match
+-- `profile::DEFAULT_*` in the service.
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS timezone TEXT NOT NULL DEFAULT 'UTC',
+    ADD COLUMN IF NOT EXISTS locale TEXT NOT NULL DEFAULT 'en-US',
+    ADD COLUMN IF NOT EXISTS currency TEXT NOT NULL DEFAULT 'EUR',
+    ADD COLUMN IF NOT EXISTS tariff_ref TEXT,
+    ADD COLUMN IF NOT EXISTS contact_email TEXT,
+    ADD COLUMN IF NOT EXISTS contact_phone TEXT;
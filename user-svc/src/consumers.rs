@@ -0,0 +1,297 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use lapin::{
+    Connection, ConnectionProperties, Consumer,
+    message::Delivery,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+};
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppState, households, messaging,
+    models::{HomeType, UnitEnergy, User},
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct UserEvent {
+    event_type: String,
+    user_id: Uuid,
+    #[serde(default)]
+    payload: Option<serde_json::Value>,
+}
+
+/// Defaults chosen by auth-svc at registration time.
+#[derive(Debug, Deserialize)]
+struct UserCreatedPayload {
+    default_unit: UnitEnergy,
+    default_home_type: HomeType,
+    default_goal: i64,
+}
+
+enum Outcome {
+    Done,
+    /// Retried once, then compensated.
+    Retry(anyhow::Error),
+    /// Retried until it succeeds; used where there is nothing to undo.
+    Requeue(anyhow::Error),
+    Compensate(String),
+}
+
+/// Consumes user lifecycle events published by auth-svc. `USER_CREATED`
+/// provisions the profile; when that fails for good, auth-svc is told to
+/// roll the registration back.
+pub fn spawn_user_events_consumer(
+    state: Arc<AppState>,
+    url: String,
+    queue: String,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match run_consumer(&state, &url, &queue).await {
+                Ok(_) => info!("user events consumer exited"),
+                Err(err) => error!(?err, "user events consumer error"),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn run_consumer(state: &AppState, url: &str, queue: &str) -> Result<(), anyhow::Error> {
+    let conn = Connection::connect(url, ConnectionProperties::default()).await?;
+    let channel = conn.create_channel().await?;
+    channel.basic_qos(20, BasicQosOptions::default()).await?;
+
+    channel
+        .queue_declare(
+            queue,
+            QueueDeclareOptions {
+                durable: true,
+                auto_delete: false,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            queue,
+            "user-events",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    process_events(&mut consumer, state).await;
+
+    Ok(())
+}
+
+async fn process_events(consumer: &mut Consumer, state: &AppState) {
+    while let Some(delivery) = consumer.next().await {
+        match delivery {
+            Ok(delivery) => {
+                let event: UserEvent = match serde_json::from_slice(&delivery.data) {
+                    Ok(event) => event,
+                    Err(err) => {
+                        error!(?err, "dropping malformed user event");
+                        ack(&delivery).await;
+                        continue;
+                    }
+                };
+
+                let outcome = match event.event_type.as_str() {
+                    "USER_CREATED" => provision_profile(state, &event).await,
+                    "USER_DELETED" => delete_profile(state, event.user_id).await,
+                    other => {
+                        debug!(event_type = other, "ignoring user event");
+                        Outcome::Done
+                    }
+                };
+
+                match outcome {
+                    Outcome::Done => ack(&delivery).await,
+                    Outcome::Requeue(err) => {
+                        warn!(?err, user_id = %event.user_id, event_type = %event.event_type, "failed to process user event; requeueing");
+                        nack(&delivery).await;
+                    }
+                    // One redelivery covers transient failures such as a
+                    // database restart; after that the registration is undone.
+                    Outcome::Retry(err) if !delivery.redelivered => {
+                        warn!(?err, user_id = %event.user_id, "provisioning failed; retrying");
+                        nack(&delivery).await;
+                    }
+                    Outcome::Retry(err) => {
+                        compensate(&delivery, state, event.user_id, &err.to_string()).await
+                    }
+                    Outcome::Compensate(reason) => {
+                        compensate(&delivery, state, event.user_id, &reason).await
+                    }
+                }
+            }
+            Err(err) => error!(?err, "user events consumer delivery error"),
+        }
+    }
+}
+
+/// Creates the profile for a newly registered user. Redelivered events are
+/// harmless: an existing profile is left untouched.
+async fn provision_profile(state: &AppState, event: &UserEvent) -> Outcome {
+    let payload = match event
+        .payload
+        .clone()
+        .map(serde_json::from_value::<UserCreatedPayload>)
+    {
+        Some(Ok(payload)) => payload,
+        Some(Err(err)) => return Outcome::Compensate(format!("invalid payload: {err}")),
+        None => return Outcome::Compensate("missing payload".into()),
+    };
+
+    match insert_profile(state, event.user_id, &payload).await {
+        Ok(true) => {
+            info!(user_id = %event.user_id, "provisioned user profile");
+            Outcome::Done
+        }
+        Ok(false) => {
+            debug!(user_id = %event.user_id, "profile already provisioned");
+            Outcome::Done
+        }
+        Err(err) => Outcome::Retry(err.into()),
+    }
+}
+
+/// Purges the profile of a deleted account and repairs the households it
+/// belonged to. `USER_DELETED` goes out on the sync queue in the same
+/// transaction. Already missing profiles are fine, so redeliveries are
+/// harmless.
+async fn delete_profile(state: &AppState, user_id: Uuid) -> Outcome {
+    match purge_profile(state, user_id).await {
+        Ok(deleted) => {
+            info!(%user_id, deleted, "purged user profile");
+            Outcome::Done
+        }
+        Err(err) => Outcome::Requeue(err.into()),
+    }
+}
+
+async fn purge_profile(state: &AppState, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let mut tx = state.db_pool.begin().await?;
+
+    let household_ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT household_id FROM household_members WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let deleted = sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    if deleted > 0 {
+        outbox::enqueue(
+            &mut tx,
+            &state.sync_queue,
+            &messaging::user_deleted(user_id),
+        )
+        .await?;
+    }
+
+    households::release_memberships(&mut tx, state, &household_ids).await?;
+
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+/// Inserts the profile and its `USER_CREATED` sync event together. Returns
+/// `false` when the profile already existed.
+async fn insert_profile(
+    state: &AppState,
+    user_id: Uuid,
+    payload: &UserCreatedPayload,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = state.db_pool.begin().await?;
+
+    let created = sqlx::query_as::<_, User>(
+        r#"
+        INSERT INTO users (id, unit_energy, home_type, goal_kwh_month)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (id) DO NOTHING
+        RETURNING id, unit_energy, home_type, goal_kwh_month, timezone, locale, currency,
+                  tariff_ref, contact_email, contact_phone, created_at, updated_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(payload.default_unit)
+    .bind(payload.default_home_type)
+    .bind(payload.default_goal)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(user) = created else {
+        return Ok(false);
+    };
+
+    outbox::enqueue(
+        &mut tx,
+        &state.sync_queue,
+        &messaging::user_event("USER_CREATED", &user),
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+async fn compensate(delivery: &Delivery, state: &AppState, user_id: Uuid, reason: &str) {
+    error!(%user_id, reason, "giving up on profile provisioning");
+
+    let enqueued = async {
+        let mut conn = state.db_pool.acquire().await?;
+        outbox::enqueue(
+            &mut conn,
+            &state.auth_events_queue,
+            &messaging::provisioning_failed(user_id, reason),
+        )
+        .await
+    }
+    .await;
+
+    match enqueued {
+        Ok(()) => ack(delivery).await,
+        Err(err) => {
+            error!(?err, "failed to enqueue USER_PROVISIONING_FAILED event");
+            nack(delivery).await;
+        }
+    }
+}
+
+async fn ack(delivery: &Delivery) {
+    if let Err(err) = delivery.ack(BasicAckOptions::default()).await {
+        error!(?err, "failed to ack user event");
+    }
+}
+
+async fn nack(delivery: &Delivery) {
+    if let Err(err) = delivery
+        .nack(BasicNackOptions {
+            requeue: true,
+            ..Default::default()
+        })
+        .await
+    {
+        error!(?err, "failed to nack user event");
+    }
+}
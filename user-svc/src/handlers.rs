@@ -2,19 +2,41 @@ use std::sync::Arc;
 
 use axum::{
     Json,
-    extract::{FromRequestParts, State},
+    extract::{FromRequestParts, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
 };
+use chrono::{DateTime, SecondsFormat, Utc};
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     AppState,
     errors::ApiError,
-    models::{CreateRequest, UpdateRequest, User, UserRole},
+    households, messaging,
+    models::{
+        CreateRequest, Household, HouseholdDetails, HouseholdRequest, HouseholdRole,
+        MemberHousehold, MemberRequest, SortOrder, UpdateRequest, User, UserPage, UserQuery,
+        UserSort,
+    },
+    scopes,
 };
 
+const MAX_HOUSEHOLD_NAME_LEN: usize = 100;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Shared by the page and count queries of `get_all`.
+const USER_FILTERS: &str = r#"
+    WHERE ($1::home_type IS NULL OR home_type = $1)
+      AND ($2::unit_energy IS NULL OR unit_energy = $2)
+      AND ($3::BIGINT IS NULL OR goal_kwh_month >= $3)
+      AND ($4::BIGINT IS NULL OR goal_kwh_month <= $4)
+      AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5)
+      AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6)
+"#;
+
 pub async fn health_check() -> impl IntoResponse {
     StatusCode::OK
 }
@@ -24,35 +46,59 @@ pub async fn create(
     user: AuthenticatedUser,
     Json(payload): Json<CreateRequest>,
 ) -> Result<Json<User>, ApiError> {
+    user.require_scope(scopes::USERS_WRITE)?;
+    let payload = payload.validate()?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
     let user_data = sqlx::query_as::<_, User>(
         r#"
-           INSERT INTO users (id, unit_energy, home_type, goal_kwh_month)
-           VALUES ($1, $2, $3, $4)
-           RETURNING id, unit_energy, home_type, goal_kwh_month, created_at, updated_at
+           INSERT INTO users (
+               id, unit_energy, home_type, goal_kwh_month, timezone, locale, currency,
+               tariff_ref, contact_email, contact_phone
+           )
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+           RETURNING id, unit_energy, home_type, goal_kwh_month, timezone, locale, currency,
+                     tariff_ref, contact_email, contact_phone, created_at, updated_at
         "#,
     )
-    .bind(&user.user_id)
-    .bind(&payload.unit_energy)
-    .bind(&payload.home_type)
-    .bind(&payload.goal_kwh_month)
-    .fetch_one(&state.db_pool)
+    .bind(user.user_id)
+    .bind(payload.unit_energy)
+    .bind(payload.home_type)
+    .bind(payload.goal_kwh_month)
+    .bind(&payload.timezone)
+    .bind(&payload.locale)
+    .bind(&payload.currency)
+    .bind(&payload.tariff_ref)
+    .bind(&payload.contact_email)
+    .bind(&payload.contact_phone)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
-        if let sqlx::Error::Database(db) = &e {
-            if db.code().as_deref() == Some("23505") {
-                return ApiError::Conflict;
-            }
+        if let sqlx::Error::Database(db) = &e
+            && db.code().as_deref() == Some("23505")
+        {
+            return ApiError::Conflict;
         }
         ApiError::Internal
     })?;
 
-    if let Err(err) = state
-        .publisher
-        .publish_user_event("USER_CREATED", &user_data)
-        .await
-    {
-        error!(?err, "failed to publish USER_CREATED event");
-    }
+    outbox::enqueue(
+        &mut tx,
+        &state.sync_queue,
+        &messaging::user_event("USER_CREATED", &user_data),
+    )
+    .await
+    .map_err(|e| {
+        error!(?e, "failed to enqueue USER_CREATED event");
+        ApiError::Internal
+    })?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
 
     Ok(Json(user_data))
 }
@@ -62,14 +108,23 @@ pub async fn update(
     user: AuthenticatedUser,
     Json(payload): Json<UpdateRequest>,
 ) -> Result<Json<User>, ApiError> {
-    let user_id = if let Some(target) = payload.user_id {
-        if user.role != UserRole::ADMIN && target != user.user_id {
-            return Err(ApiError::Unauthorized("Invalid role".to_string()));
+    let user_id = match payload.user_id {
+        Some(target) if target != user.user_id => {
+            user.require_scope(scopes::USERS_ADMIN)?;
+            target
+        }
+        _ => {
+            user.require_scope(scopes::USERS_WRITE)?;
+            user.user_id
         }
-        target
-    } else {
-        user.user_id
     };
+    let payload = payload.validate()?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
 
     let updated_user = sqlx::query_as::<_, User>(
         r#"
@@ -78,6 +133,12 @@ pub async fn update(
             unit_energy     = COALESCE($2, unit_energy),
             home_type       = COALESCE($3, home_type),
             goal_kwh_month  = COALESCE($4, goal_kwh_month),
+            timezone        = COALESCE($5, timezone),
+            locale          = COALESCE($6, locale),
+            currency        = COALESCE($7, currency),
+            tariff_ref      = CASE WHEN $8 THEN $9 ELSE tariff_ref END,
+            contact_email   = CASE WHEN $10 THEN $11 ELSE contact_email END,
+            contact_phone   = CASE WHEN $12 THEN $13 ELSE contact_phone END,
             updated_at      = NOW()
         WHERE id = $1
         RETURNING
@@ -85,15 +146,30 @@ pub async fn update(
             unit_energy,
             home_type,
             goal_kwh_month,
+            timezone,
+            locale,
+            currency,
+            tariff_ref,
+            contact_email,
+            contact_phone,
             created_at,
             updated_at
         "#,
     )
-    .bind(&user_id)
-    .bind(&payload.unit_energy)
-    .bind(&payload.home_type)
-    .bind(&payload.goal_kwh_month)
-    .fetch_one(&state.db_pool)
+    .bind(user_id)
+    .bind(payload.unit_energy)
+    .bind(payload.home_type)
+    .bind(payload.goal_kwh_month)
+    .bind(&payload.timezone)
+    .bind(&payload.locale)
+    .bind(&payload.currency)
+    .bind(payload.tariff_ref.is_some())
+    .bind(payload.tariff_ref.flatten())
+    .bind(payload.contact_email.is_some())
+    .bind(payload.contact_email.flatten())
+    .bind(payload.contact_phone.is_some())
+    .bind(payload.contact_phone.flatten())
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| match e {
         sqlx::Error::RowNotFound => ApiError::NotFound("user id not found".to_string()),
@@ -103,49 +179,172 @@ pub async fn update(
         _ => ApiError::Internal,
     })?;
 
-    if let Err(err) = state
-        .publisher
-        .publish_user_event("USER_UPDATED", &updated_user)
-        .await
-    {
-        error!(?err, "failed to publish USER_UPDATED event");
-    }
+    outbox::enqueue(
+        &mut tx,
+        &state.sync_queue,
+        &messaging::user_event("USER_UPDATED", &updated_user),
+    )
+    .await
+    .map_err(|e| {
+        error!(?e, "failed to enqueue USER_UPDATED event");
+        ApiError::Internal
+    })?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
 
     Ok(Json(updated_user))
 }
 
+/// One page of profiles, newest first unless `sort`/`order` say otherwise.
+/// Paging is keyset-based on the sort column and `id`, so rows inserted
+/// meanwhile neither shift nor repeat later pages.
 pub async fn get_all(
     State(state): State<Arc<AppState>>,
-    user: AuthenticatedUser,
-) -> Result<Json<Vec<User>>, ApiError> {
-    if user.role != UserRole::ADMIN {
-        return Err(ApiError::Unauthorized("Invalid role".to_string()));
+    Query(query): Query<UserQuery>,
+) -> Result<Json<UserPage>, ApiError> {
+    if let (Some(min), Some(max)) = (query.goal_min, query.goal_max)
+        && min > max
+    {
+        return Err(ApiError::BadRequest(
+            "goal_min must not exceed goal_max".to_string(),
+        ));
     }
+    let limit = match query.limit {
+        None => DEFAULT_PAGE_SIZE,
+        Some(limit) if limit > 0 => limit.min(MAX_PAGE_SIZE),
+        Some(_) => return Err(ApiError::BadRequest("limit must be positive".to_string())),
+    };
+    let after = query
+        .cursor
+        .as_deref()
+        .map(|cursor| decode_cursor(cursor, query.sort, query.order))
+        .transpose()?;
 
-    let users = sqlx::query_as::<_, User>(
+    let column = query.sort.column();
+    let cast = match query.sort {
+        UserSort::CreatedAt | UserSort::UpdatedAt => "TIMESTAMPTZ",
+        UserSort::GoalKwhMonth => "BIGINT",
+    };
+    let (direction, comparison) = match query.order {
+        SortOrder::Asc => ("ASC", ">"),
+        SortOrder::Desc => ("DESC", "<"),
+    };
+    // Only the whitelisted names above are interpolated; values are bound.
+    let sql = format!(
         r#"
         SELECT
             id,
             unit_energy,
             home_type,
             goal_kwh_month,
+            timezone,
+            locale,
+            currency,
+            tariff_ref,
+            contact_email,
+            contact_phone,
             created_at,
             updated_at
         FROM users
-        ORDER BY created_at DESC
-        "#,
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+        {USER_FILTERS}
+          AND ($7::TEXT IS NULL OR ({column}, id) {comparison} ($7::{cast}, $8))
+        ORDER BY {column} {direction}, id {direction}
+        LIMIT $9
+        "#
+    );
+
+    let mut items = sqlx::query_as::<_, User>(&sql)
+        .bind(query.home_type)
+        .bind(query.unit_energy)
+        .bind(query.goal_min)
+        .bind(query.goal_max)
+        .bind(query.created_from)
+        .bind(query.created_to)
+        .bind(after.as_ref().map(|(value, _)| value.as_str()))
+        .bind(after.as_ref().map(|(_, id)| *id))
+        .bind(limit + 1)
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(|e| {
+            error!(?e, "failed to list users");
+            ApiError::Internal
+        })?;
 
-    Ok(Json(users))
+    let total = sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM users {USER_FILTERS}"))
+        .bind(query.home_type)
+        .bind(query.unit_energy)
+        .bind(query.goal_min)
+        .bind(query.goal_max)
+        .bind(query.created_from)
+        .bind(query.created_to)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|e| {
+            error!(?e, "failed to count users");
+            ApiError::Internal
+        })?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items
+            .last()
+            .map(|last| encode_cursor(last, query.sort, query.order))
+    } else {
+        None
+    };
+
+    Ok(Json(UserPage {
+        items,
+        total,
+        next_cursor,
+    }))
+}
+
+/// `<sort>:<order>:<value>:<id>` of the last row of a page. Naming the sort
+/// and its direction keeps a cursor from being replayed against another
+/// ordering.
+fn encode_cursor(user: &User, sort: UserSort, order: SortOrder) -> String {
+    let timestamp = |ts: &DateTime<Utc>| ts.to_rfc3339_opts(SecondsFormat::Micros, true);
+    let value = match sort {
+        UserSort::CreatedAt => timestamp(&user.created_at),
+        UserSort::UpdatedAt => timestamp(&user.updated_at),
+        UserSort::GoalKwhMonth => user.goal_kwh_month.to_string(),
+    };
+    format!("{}:{}:{value}:{}", sort.column(), order.as_str(), user.id)
+}
+
+/// Splits a cursor into the sort value, checked to parse as the column's
+/// type, and the row id.
+fn decode_cursor(
+    cursor: &str,
+    sort: UserSort,
+    order: SortOrder,
+) -> Result<(String, Uuid), ApiError> {
+    let invalid = || ApiError::BadRequest("invalid cursor for this sort".to_string());
+
+    let (column, rest) = cursor.split_once(':').ok_or_else(invalid)?;
+    let (direction, rest) = rest.split_once(':').ok_or_else(invalid)?;
+    let (value, id) = rest.rsplit_once(':').ok_or_else(invalid)?;
+    if column != sort.column() || direction != order.as_str() {
+        return Err(invalid());
+    }
+    let valid = match sort {
+        UserSort::CreatedAt | UserSort::UpdatedAt => DateTime::parse_from_rfc3339(value).is_ok(),
+        UserSort::GoalKwhMonth => value.parse::<i64>().is_ok(),
+    };
+    if !valid {
+        return Err(invalid());
+    }
+    let id = Uuid::parse_str(id).map_err(|_| invalid())?;
+    Ok((value.to_string(), id))
 }
 
 pub async fn me(
     State(state): State<Arc<AppState>>,
     user: AuthenticatedUser,
 ) -> Result<Json<User>, ApiError> {
+    user.require_scope(scopes::USERS_READ)?;
+
     let me = sqlx::query_as::<_, User>(
         r#"
         SELECT
@@ -153,13 +352,19 @@ pub async fn me(
             unit_energy,
             home_type,
             goal_kwh_month,
+            timezone,
+            locale,
+            currency,
+            tariff_ref,
+            contact_email,
+            contact_phone,
             created_at,
             updated_at
         FROM users
         WHERE id = $1
         "#,
     )
-    .bind(&user.user_id)
+    .bind(user.user_id)
     .fetch_one(&state.db_pool)
     .await
     .map_err(|e| match e {
@@ -170,9 +375,356 @@ pub async fn me(
     Ok(Json(me))
 }
 
+pub async fn list_households(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<MemberHousehold>>, ApiError> {
+    user.require_scope(scopes::USERS_READ)?;
+
+    let households = sqlx::query_as::<_, MemberHousehold>(
+        r#"
+        SELECT h.id, h.name, h.created_at, h.updated_at, m.role
+        FROM households h
+        JOIN household_members m ON m.household_id = h.id
+        WHERE m.user_id = $1
+        ORDER BY h.created_at
+        "#,
+    )
+    .bind(user.user_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        error!(?e, "failed to list households");
+        ApiError::Internal
+    })?;
+
+    Ok(Json(households))
+}
+
+pub async fn create_household(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Json(payload): Json<HouseholdRequest>,
+) -> Result<(StatusCode, Json<HouseholdDetails>), ApiError> {
+    user.require_scope(scopes::USERS_WRITE)?;
+    let name = household_name(&payload.name)?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    let household = sqlx::query_as::<_, Household>(
+        "INSERT INTO households (name) VALUES ($1) RETURNING id, name, created_at, updated_at",
+    )
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| ApiError::Internal)?;
+
+    sqlx::query("INSERT INTO household_members (household_id, user_id, role) VALUES ($1, $2, $3)")
+        .bind(household.id)
+        .bind(user.user_id)
+        .bind(HouseholdRole::Owner)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23503") => {
+                ApiError::NotFound("user id not found".to_string())
+            }
+            _ => ApiError::Internal,
+        })?;
+
+    households::publish_members(&mut tx, &state, household.id)
+        .await
+        .map_err(|e| {
+            error!(?e, "failed to enqueue HOUSEHOLD_UPDATED event");
+            ApiError::Internal
+        })?;
+
+    let members = households::members(&mut *tx, household.id)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(HouseholdDetails { household, members }),
+    ))
+}
+
+pub async fn get_household(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<HouseholdDetails>, ApiError> {
+    user.require_scope(scopes::USERS_READ)?;
+    household_role(&state, &user, id).await?;
+
+    Ok(Json(household_details(&state, id).await?))
+}
+
+pub async fn rename_household(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<HouseholdRequest>,
+) -> Result<Json<HouseholdDetails>, ApiError> {
+    user.require_scope(scopes::USERS_WRITE)?;
+    require_owner(household_role(&state, &user, id).await?)?;
+    let name = household_name(&payload.name)?;
+
+    sqlx::query("UPDATE households SET name = $2, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(name)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    Ok(Json(household_details(&state, id).await?))
+}
+
+pub async fn delete_household(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    user.require_scope(scopes::USERS_WRITE)?;
+    require_owner(household_role(&state, &user, id).await?)?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    if !households::delete(&mut tx, &state, id).await.map_err(|e| {
+        error!(?e, "failed to delete household");
+        ApiError::Internal
+    })? {
+        return Err(ApiError::NotFound("household not found".to_string()));
+    }
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Adds `member_id` to the household or changes their role. Owners only.
+pub async fn put_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path((id, member_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<MemberRequest>,
+) -> Result<Json<HouseholdDetails>, ApiError> {
+    user.require_scope(scopes::USERS_WRITE)?;
+    require_owner(household_role(&state, &user, id).await?)?;
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    if !households::lock(&mut tx, id)
+        .await
+        .map_err(|_| ApiError::Internal)?
+    {
+        return Err(ApiError::NotFound("household not found".to_string()));
+    }
+
+    let current = households::role_of(&mut *tx, id, member_id)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+    if current == Some(HouseholdRole::Owner)
+        && payload.role != HouseholdRole::Owner
+        && !households::has_other_owner(&mut tx, id, member_id)
+            .await
+            .map_err(|_| ApiError::Internal)?
+    {
+        return Err(ApiError::BadRequest(
+            "a household needs at least one owner".to_string(),
+        ));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO household_members (household_id, user_id, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (household_id, user_id) DO UPDATE SET role = EXCLUDED.role
+        "#,
+    )
+    .bind(id)
+    .bind(member_id)
+    .bind(payload.role)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23503") => {
+            ApiError::NotFound("user id not found".to_string())
+        }
+        _ => ApiError::Internal,
+    })?;
+
+    households::publish_members(&mut tx, &state, id)
+        .await
+        .map_err(|e| {
+            error!(?e, "failed to enqueue HOUSEHOLD_UPDATED event");
+            ApiError::Internal
+        })?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok(Json(household_details(&state, id).await?))
+}
+
+/// Removes `member_id` from the household. Owners remove anyone, every
+/// member may leave.
+pub async fn remove_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path((id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    user.require_scope(scopes::USERS_WRITE)?;
+    let role = household_role(&state, &user, id).await?;
+    if member_id != user.user_id {
+        require_owner(role)?;
+    }
+
+    let mut tx = state
+        .db_pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    if !households::lock(&mut tx, id)
+        .await
+        .map_err(|_| ApiError::Internal)?
+    {
+        return Err(ApiError::NotFound("household not found".to_string()));
+    }
+
+    let current = households::role_of(&mut *tx, id, member_id)
+        .await
+        .map_err(|_| ApiError::Internal)?
+        .ok_or(ApiError::NotFound("member not found".to_string()))?;
+    if current == HouseholdRole::Owner
+        && !households::has_other_owner(&mut tx, id, member_id)
+            .await
+            .map_err(|_| ApiError::Internal)?
+    {
+        return Err(ApiError::BadRequest(
+            "the last owner cannot leave; appoint another owner or delete the household"
+                .to_string(),
+        ));
+    }
+
+    sqlx::query("DELETE FROM household_members WHERE household_id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(member_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    households::publish_members(&mut tx, &state, id)
+        .await
+        .map_err(|e| {
+            error!(?e, "failed to enqueue HOUSEHOLD_UPDATED event");
+            ApiError::Internal
+        })?;
+
+    tx.commit().await.map_err(|_| ApiError::Internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The caller's role in household `id`; admins act as owners. Households
+/// the caller does not belong to answer 404, so they cannot be probed.
+async fn household_role(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    id: Uuid,
+) -> Result<HouseholdRole, ApiError> {
+    let not_found = || ApiError::NotFound("household not found".to_string());
+
+    if user.has_scope(scopes::USERS_ADMIN) {
+        let exists =
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM households WHERE id = $1)")
+                .bind(id)
+                .fetch_one(&state.db_pool)
+                .await
+                .map_err(|_| ApiError::Internal)?;
+        return if exists {
+            Ok(HouseholdRole::Owner)
+        } else {
+            Err(not_found())
+        };
+    }
+
+    households::role_of(&state.db_pool, id, user.user_id)
+        .await
+        .map_err(|_| ApiError::Internal)?
+        .ok_or_else(not_found)
+}
+
+fn require_owner(role: HouseholdRole) -> Result<(), ApiError> {
+    if role == HouseholdRole::Owner {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "only household owners can do this".to_string(),
+        ))
+    }
+}
+
+fn household_name(name: &str) -> Result<&str, ApiError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_HOUSEHOLD_NAME_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "name must be 1 to {MAX_HOUSEHOLD_NAME_LEN} characters"
+        )));
+    }
+    Ok(name)
+}
+
+async fn household_details(state: &AppState, id: Uuid) -> Result<HouseholdDetails, ApiError> {
+    let household = sqlx::query_as::<_, Household>(
+        "SELECT id, name, created_at, updated_at FROM households WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| ApiError::Internal)?
+    .ok_or(ApiError::NotFound("household not found".to_string()))?;
+
+    let members = households::members(&state.db_pool, id)
+        .await
+        .map_err(|_| ApiError::Internal)?;
+
+    Ok(HouseholdDetails { household, members })
+}
+
+/// Principal forwarded by the gateway. `scopes` come from `X-Scopes`, which
+/// auth-svc fills from the role of a user or the grant of a service client.
 pub struct AuthenticatedUser {
     pub user_id: Uuid,
-    pub role: UserRole,
+    pub scopes: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!("missing scope {scope}")))
+        }
+    }
 }
 
 impl<S> FromRequestParts<S> for AuthenticatedUser
@@ -195,20 +747,94 @@ where
         let user_id = Uuid::parse_str(user_id_str)
             .map_err(|_| ApiError::Unauthorized("Invalid UUID in X-User-Id".to_string()))?;
 
-        let role_header = parts
+        let scopes = parts
             .headers
-            .get("x-user-role")
-            .ok_or(ApiError::Unauthorized("Missing X-User-Role".to_string()))?;
-        let role_str = role_header
-            .to_str()
-            .map_err(|_| ApiError::Unauthorized("Invalid X-User-Role header".to_string()))?;
+            .get("x-scopes")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
 
-        let role = match role_str.to_uppercase().as_str() {
-            "ADMIN" => UserRole::ADMIN,
-            "CLIENT" => UserRole::CLIENT,
-            _ => return Err(ApiError::Unauthorized("Invalid role".to_string())),
-        };
+        Ok(AuthenticatedUser { user_id, scopes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::models::{HomeType, UnitEnergy};
+
+    fn user() -> User {
+        let created_at = Utc.with_ymd_and_hms(2026, 10, 18, 12, 30, 15).unwrap();
+        User {
+            id: Uuid::new_v4(),
+            unit_energy: UnitEnergy::Kwh,
+            home_type: HomeType::House,
+            goal_kwh_month: 250,
+            timezone: "UTC".to_string(),
+            locale: "en-US".to_string(),
+            currency: "EUR".to_string(),
+            tariff_ref: None,
+            contact_email: None,
+            contact_phone: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips_timestamps_containing_colons() {
+        let user = user();
+        let cursor = encode_cursor(&user, UserSort::CreatedAt, SortOrder::Desc);
+
+        let (value, id) =
+            decode_cursor(&cursor, UserSort::CreatedAt, SortOrder::Desc).expect("cursor decodes");
+        assert_eq!(value, "2026-10-18T12:30:15.000000Z");
+        assert_eq!(id, user.id);
+    }
+
+    #[test]
+    fn cursor_round_trips_numeric_sort() {
+        let user = user();
+        let cursor = encode_cursor(&user, UserSort::GoalKwhMonth, SortOrder::Asc);
+
+        let decoded =
+            decode_cursor(&cursor, UserSort::GoalKwhMonth, SortOrder::Asc).expect("cursor decodes");
+        assert_eq!(decoded, ("250".to_string(), user.id));
+    }
+
+    #[test]
+    fn cursor_rejects_another_column() {
+        let cursor = encode_cursor(&user(), UserSort::CreatedAt, SortOrder::Desc);
+        assert!(decode_cursor(&cursor, UserSort::UpdatedAt, SortOrder::Desc).is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_another_direction() {
+        let cursor = encode_cursor(&user(), UserSort::CreatedAt, SortOrder::Desc);
+        assert!(decode_cursor(&cursor, UserSort::CreatedAt, SortOrder::Asc).is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_malformed_values() {
+        let id = Uuid::new_v4();
+        let sort = UserSort::GoalKwhMonth;
+        let order = SortOrder::Desc;
 
-        Ok(AuthenticatedUser { user_id, role })
+        assert!(decode_cursor("", sort, order).is_err());
+        assert!(decode_cursor("goal_kwh_month:desc", sort, order).is_err());
+        assert!(decode_cursor(&format!("goal_kwh_month:desc:abc:{id}"), sort, order).is_err());
+        assert!(decode_cursor("goal_kwh_month:desc:250:not-a-uuid", sort, order).is_err());
+        assert!(
+            decode_cursor(
+                &format!("created_at:desc:yesterday:{id}"),
+                UserSort::CreatedAt,
+                order
+            )
+            .is_err()
+        );
+        // Cursors from before the direction was part of them.
+        assert!(decode_cursor(&format!("goal_kwh_month:250:{id}"), sort, order).is_err());
     }
 }
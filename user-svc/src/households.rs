@@ -0,0 +1,153 @@
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::{
+    AppState, messaging,
+    models::{HouseholdMember, HouseholdRole},
+};
+
+pub async fn role_of<'e>(
+    executor: impl PgExecutor<'e>,
+    household_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<HouseholdRole>, sqlx::Error> {
+    sqlx::query_scalar::<_, HouseholdRole>(
+        "SELECT role FROM household_members WHERE household_id = $1 AND user_id = $2",
+    )
+    .bind(household_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn members<'e>(
+    executor: impl PgExecutor<'e>,
+    household_id: Uuid,
+) -> Result<Vec<HouseholdMember>, sqlx::Error> {
+    sqlx::query_as::<_, HouseholdMember>(
+        r#"
+        SELECT user_id, role, added_at
+        FROM household_members
+        WHERE household_id = $1
+        ORDER BY role, added_at
+        "#,
+    )
+    .bind(household_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// Locks the household row for the rest of the caller's transaction, so
+/// concurrent membership changes cannot both pass the last-owner check.
+/// Returns `false` if the household does not exist.
+pub async fn lock(conn: &mut PgConnection, household_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM households WHERE id = $1 FOR UPDATE")
+        .bind(household_id)
+        .fetch_optional(conn)
+        .await
+        .map(|id| id.is_some())
+}
+
+/// Whether an owner other than `user_id` remains, so `user_id` may step down.
+pub async fn has_other_owner(
+    conn: &mut PgConnection,
+    household_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM household_members
+            WHERE household_id = $1 AND user_id <> $2 AND role = 'OWNER'
+        )
+        "#,
+    )
+    .bind(household_id)
+    .bind(user_id)
+    .fetch_one(conn)
+    .await
+}
+
+/// Bumps the household's version and records its membership snapshot for
+/// device-svc and monitor-svc in the caller's transaction.
+pub async fn publish_members(
+    conn: &mut PgConnection,
+    state: &AppState,
+    household_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let version = sqlx::query_scalar::<_, i64>(
+        r#"
+        UPDATE households
+        SET version = version + 1, updated_at = NOW()
+        WHERE id = $1
+        RETURNING version
+        "#,
+    )
+    .bind(household_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let members = members(&mut *conn, household_id).await?;
+    let event = messaging::household_event("HOUSEHOLD_UPDATED", household_id, version, &members);
+
+    for queue in [&state.sync_queue, &state.device_events_queue] {
+        outbox::enqueue(conn, queue, &event).await?;
+    }
+    Ok(())
+}
+
+/// Deletes the household and records `HOUSEHOLD_DELETED`, after which
+/// device-svc detaches its devices. Returns `false` if it did not exist.
+pub async fn delete(
+    conn: &mut PgConnection,
+    state: &AppState,
+    household_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let version =
+        sqlx::query_scalar::<_, i64>("DELETE FROM households WHERE id = $1 RETURNING version")
+            .bind(household_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let Some(version) = version else {
+        return Ok(false);
+    };
+
+    let event = messaging::household_event("HOUSEHOLD_DELETED", household_id, version + 1, &[]);
+    for queue in [&state.sync_queue, &state.device_events_queue] {
+        outbox::enqueue(conn, queue, &event).await?;
+    }
+    Ok(true)
+}
+
+/// Repairs the households `user_id` belonged to after the account is gone:
+/// an empty household is deleted, one left without an owner promotes its
+/// most senior remaining member.
+pub async fn release_memberships(
+    conn: &mut PgConnection,
+    state: &AppState,
+    household_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    for &household_id in household_ids {
+        if !lock(conn, household_id).await? {
+            continue;
+        }
+        let remaining = members(&mut *conn, household_id).await?;
+        if remaining.is_empty() {
+            delete(conn, state, household_id).await?;
+            continue;
+        }
+
+        if !remaining.iter().any(|m| m.role == HouseholdRole::Owner) {
+            // `members` lists members before viewers, oldest first.
+            sqlx::query(
+                "UPDATE household_members SET role = 'OWNER' WHERE household_id = $1 AND user_id = $2",
+            )
+            .bind(household_id)
+            .bind(remaining[0].user_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        publish_members(conn, state, household_id).await?;
+    }
+    Ok(())
+}
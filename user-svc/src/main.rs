@@ -1,22 +1,27 @@
 use std::sync::Arc;
 
-use axum::Router;
+use axum::{Router, middleware::from_fn_with_state};
 use routes::create_route;
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod consumers;
 mod errors;
 mod handlers;
+mod households;
 mod messaging;
-mod middleware;
 mod models;
+mod profile;
 mod routes;
+mod scopes;
 
-use messaging::EventPublisher;
+use outbox::EventPublisher;
 
 struct AppState {
     db_pool: PgPool,
-    publisher: EventPublisher,
+    sync_queue: String,
+    device_events_queue: String,
+    auth_events_queue: String,
 }
 
 static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
@@ -34,6 +39,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let broker_url =
         std::env::var("SYNC_BROKER_URL").expect("SYNC_BROKER_URL must be set for user-svc.");
     let sync_queue = std::env::var("SYNC_QUEUE").unwrap_or_else(|_| "sync.events".into());
+    let user_events_queue =
+        std::env::var("USER_EVENTS_QUEUE").unwrap_or_else(|_| "user.events".into());
+    let device_events_queue =
+        std::env::var("DEVICE_EVENTS_QUEUE").unwrap_or_else(|_| "device.events".into());
+    let auth_events_queue =
+        std::env::var("AUTH_EVENTS_QUEUE").unwrap_or_else(|_| "auth.events".into());
 
     let pool = PgPoolOptions::new()
         .max_connections(20)
@@ -42,15 +53,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     MIGRATOR.run(&pool).await?;
 
-    let publisher = EventPublisher::new(broker_url, sync_queue).await?;
+    let publisher = EventPublisher::new(
+        broker_url.clone(),
+        vec![
+            sync_queue.clone(),
+            device_events_queue.clone(),
+            auth_events_queue.clone(),
+        ],
+    );
+    let _relay_handle = outbox::spawn_relay(pool.clone(), publisher);
 
     let shared_state = Arc::new(AppState {
         db_pool: pool,
-        publisher,
+        sync_queue,
+        device_events_queue,
+        auth_events_queue,
     });
 
+    let _user_events_handle =
+        consumers::spawn_user_events_consumer(shared_state.clone(), broker_url, user_events_queue);
+
     let app = Router::new()
         .merge(create_route())
+        .layer(from_fn_with_state(
+            impersonation::read_only_from_env(),
+            impersonation::guard,
+        ))
         .with_state(shared_state.clone());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
@@ -3,35 +3,33 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq)]
-pub enum UserRole {
-    CLIENT,
-    ADMIN,
-}
+use crate::{errors::ApiError, profile};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "UPPERCASE")]
 #[sqlx(type_name = "unit_energy", rename_all = "UPPERCASE")]
 pub enum UnitEnergy {
-    KWH,
-    WH,
+    Kwh,
+    Wh,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "UPPERCASE")]
 #[sqlx(type_name = "home_type", rename_all = "UPPERCASE")]
 pub enum HomeType {
-    APARTMENT,
-    HOUSE,
-    OFFICE,
-    INDUSTRIAL,
+    Apartment,
+    House,
+    Office,
+    Industrial,
 }
 
 impl fmt::Display for HomeType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            HomeType::APARTMENT => write!(f, "APARTMENT"),
-            HomeType::HOUSE => write!(f, "HOUSE"),
-            HomeType::OFFICE => write!(f, "OFFICE"),
-            HomeType::INDUSTRIAL => write!(f, "INDUSTRIAL"),
+            HomeType::Apartment => write!(f, "APARTMENT"),
+            HomeType::House => write!(f, "HOUSE"),
+            HomeType::Office => write!(f, "OFFICE"),
+            HomeType::Industrial => write!(f, "INDUSTRIAL"),
         }
     }
 }
@@ -39,18 +37,38 @@ impl fmt::Display for HomeType {
 impl fmt::Display for UnitEnergy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            UnitEnergy::KWH => write!(f, "KWH"),
-            UnitEnergy::WH => write!(f, "WH"),
+            UnitEnergy::Kwh => write!(f, "KWH"),
+            UnitEnergy::Wh => write!(f, "WH"),
         }
     }
 }
 
+/// Role of a user within a household. Owners manage the household and its
+/// members, members also control its devices, viewers only read.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "UPPERCASE")]
+#[sqlx(type_name = "household_role", rename_all = "UPPERCASE")]
+pub enum HouseholdRole {
+    Owner,
+    Member,
+    Viewer,
+}
+
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
     pub id: Uuid,
     pub unit_energy: UnitEnergy,
     pub home_type: HomeType,
     pub goal_kwh_month: i64,
+    /// IANA zone used to bucket consumption into the user's days.
+    pub timezone: String,
+    pub locale: String,
+    /// ISO 4217 code consumption is priced in.
+    pub currency: String,
+    /// Electricity tariff in the billing system, if one is assigned.
+    pub tariff_ref: Option<String>,
+    pub contact_email: Option<String>,
+    pub contact_phone: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -60,6 +78,50 @@ pub struct CreateRequest {
     pub unit_energy: UnitEnergy,
     pub home_type: HomeType,
     pub goal_kwh_month: i64,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub currency: Option<String>,
+    pub tariff_ref: Option<String>,
+    pub contact_email: Option<String>,
+    pub contact_phone: Option<String>,
+}
+
+impl CreateRequest {
+    /// Checks the settings and fills the regional defaults, returning the
+    /// values as they are stored.
+    pub fn validate(self) -> Result<Self, ApiError> {
+        Ok(Self {
+            timezone: Some(profile::timezone(
+                self.timezone
+                    .as_deref()
+                    .unwrap_or(profile::DEFAULT_TIMEZONE),
+            )?),
+            locale: Some(profile::locale(
+                self.locale.as_deref().unwrap_or(profile::DEFAULT_LOCALE),
+            )?),
+            currency: Some(profile::currency(
+                self.currency
+                    .as_deref()
+                    .unwrap_or(profile::DEFAULT_CURRENCY),
+            )?),
+            tariff_ref: self
+                .tariff_ref
+                .as_deref()
+                .map(profile::tariff_ref)
+                .transpose()?,
+            contact_email: self
+                .contact_email
+                .as_deref()
+                .map(profile::contact_email)
+                .transpose()?,
+            contact_phone: self
+                .contact_phone
+                .as_deref()
+                .map(profile::contact_phone)
+                .transpose()?,
+            ..self
+        })
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -68,4 +130,161 @@ pub struct UpdateRequest {
     pub unit_energy: Option<UnitEnergy>,
     pub home_type: Option<HomeType>,
     pub goal_kwh_month: Option<i64>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub currency: Option<String>,
+    /// For the optional settings, absent leaves them unchanged and `null`
+    /// clears them.
+    #[serde(default, deserialize_with = "present")]
+    pub tariff_ref: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub contact_email: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub contact_phone: Option<Option<String>>,
+}
+
+impl UpdateRequest {
+    /// Checks the settings being changed, returning them as they are stored.
+    pub fn validate(self) -> Result<Self, ApiError> {
+        fn clearable(
+            value: Option<Option<String>>,
+            check: fn(&str) -> Result<String, ApiError>,
+        ) -> Result<Option<Option<String>>, ApiError> {
+            value
+                .map(|v| v.as_deref().map(check).transpose())
+                .transpose()
+        }
+
+        Ok(Self {
+            timezone: self
+                .timezone
+                .as_deref()
+                .map(profile::timezone)
+                .transpose()?,
+            locale: self.locale.as_deref().map(profile::locale).transpose()?,
+            currency: self
+                .currency
+                .as_deref()
+                .map(profile::currency)
+                .transpose()?,
+            tariff_ref: clearable(self.tariff_ref, profile::tariff_ref)?,
+            contact_email: clearable(self.contact_email, profile::contact_email)?,
+            contact_phone: clearable(self.contact_phone, profile::contact_phone)?,
+            ..self
+        })
+    }
+}
+
+/// Tells an explicit `null` apart from a missing field.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Filters, sorting and paging of `GET /get_all`. Date bounds are
+/// inclusive `from`, exclusive `to`.
+#[derive(Debug, Deserialize)]
+pub struct UserQuery {
+    pub home_type: Option<HomeType>,
+    pub unit_energy: Option<UnitEnergy>,
+    pub goal_min: Option<i64>,
+    pub goal_max: Option<i64>,
+    pub created_from: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_to: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub sort: UserSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// `next_cursor` of the previous page, with the same filters and sort.
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    GoalKwhMonth,
+}
+
+impl UserSort {
+    pub fn column(self) -> &'static str {
+        match self {
+            UserSort::CreatedAt => "created_at",
+            UserSort::UpdatedAt => "updated_at",
+            UserSort::GoalKwhMonth => "goal_kwh_month",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserPage {
+    pub items: Vec<User>,
+    /// Number of profiles matching the filters, across all pages.
+    pub total: i64,
+    /// Pass as `cursor` to fetch the next page; `None` on the last one.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Household {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct HouseholdMember {
+    pub user_id: Uuid,
+    pub role: HouseholdRole,
+    pub added_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A household as listed for one of its members, with the member's role.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct MemberHousehold {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub household: Household,
+    pub role: HouseholdRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HouseholdDetails {
+    #[serde(flatten)]
+    pub household: Household,
+    pub members: Vec<HouseholdMember>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HouseholdRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberRequest {
+    pub role: HouseholdRole,
 }
@@ -0,0 +1,246 @@
+//! Validation of the regional, billing and contact settings of a profile.
+//! Each check returns the value in the form it is stored in.
+
+use chrono::{Days, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::errors::ApiError;
+
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+pub const DEFAULT_LOCALE: &str = "en-US";
+pub const DEFAULT_CURRENCY: &str = "EUR";
+
+const MAX_TARIFF_REF_LEN: usize = 64;
+const MAX_EMAIL_LEN: usize = 254;
+
+/// Active ISO 4217 currency codes, sorted. Fund, precious metal and test
+/// codes are left out since nothing is billed in them.
+const CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BHD",
+    "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF",
+    "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN",
+    "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD",
+    "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES",
+    "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL",
+    "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN",
+    "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP",
+    "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK",
+    "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT",
+    "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VED", "VES",
+    "VND", "VUV", "WST", "XAF", "XCD", "XCG", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWG",
+];
+
+/// An IANA zone such as `Europe/Bucharest`, in its canonical spelling.
+/// monitor-svc keeps consumption in UTC hours, so zones that are not a whole
+/// number of hours from UTC during the coming year (`Asia/Kolkata`,
+/// `Australia/Adelaide`) are rejected rather than reported shifted.
+pub fn timezone(value: &str) -> Result<String, ApiError> {
+    let tz = value
+        .trim()
+        .parse::<Tz>()
+        .map_err(|_| invalid("timezone must be an IANA time zone like Europe/Bucharest"))?;
+
+    let now = Utc::now().naive_utc();
+    let whole_hours = (0..12).all(|month| {
+        let at = now + Days::new(month * 30);
+        tz.offset_from_utc_datetime(&at).fix().local_minus_utc() % 3600 == 0
+    });
+    if !whole_hours {
+        return Err(invalid(
+            "timezone must be a whole number of hours from UTC; zones like Asia/Kolkata are not supported",
+        ));
+    }
+    Ok(tz.name().to_string())
+}
+
+/// A BCP 47 tag of the form `language[-Script][-REGION]`, e.g. `ro-RO`.
+/// `_` is accepted as separator; the result uses the conventional casing.
+pub fn locale(value: &str) -> Result<String, ApiError> {
+    let err = || invalid("locale must be a language tag like en-US");
+
+    let mut parts = value.trim().split(['-', '_']);
+    let language = parts.next().unwrap_or_default();
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(err());
+    }
+    let mut tag = language.to_ascii_lowercase();
+
+    let mut next = parts.next();
+    if let Some(script) =
+        next.filter(|p| p.len() == 4 && p.chars().all(|c| c.is_ascii_alphabetic()))
+    {
+        tag.push('-');
+        tag.push_str(&script[..1].to_ascii_uppercase());
+        tag.push_str(&script[1..].to_ascii_lowercase());
+        next = parts.next();
+    }
+    if let Some(region) = next {
+        let letters = region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic());
+        let digits = region.len() == 3 && region.chars().all(|c| c.is_ascii_digit());
+        if !letters && !digits {
+            return Err(err());
+        }
+        tag.push('-');
+        tag.push_str(&region.to_ascii_uppercase());
+    }
+    if parts.next().is_some() {
+        return Err(err());
+    }
+    Ok(tag)
+}
+
+/// An active ISO 4217 alphabetic code, upper-cased.
+pub fn currency(value: &str) -> Result<String, ApiError> {
+    let value = value.trim().to_ascii_uppercase();
+    if CURRENCIES.binary_search(&value.as_str()).is_err() {
+        return Err(invalid("currency must be an ISO 4217 code like EUR"));
+    }
+    Ok(value)
+}
+
+/// Reference of the electricity tariff in the billing system.
+pub fn tariff_ref(value: &str) -> Result<String, ApiError> {
+    let value = value.trim();
+    if value.is_empty()
+        || value.len() > MAX_TARIFF_REF_LEN
+        || !value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+    {
+        return Err(invalid(&format!(
+            "tariff_ref must be 1 to {MAX_TARIFF_REF_LEN} letters, digits or - _ . :"
+        )));
+    }
+    Ok(value.to_string())
+}
+
+/// A plausible address; deliverability is only known once we send to it.
+pub fn contact_email(value: &str) -> Result<String, ApiError> {
+    let value = value.trim();
+    let valid = value.len() <= MAX_EMAIL_LEN
+        && !value.chars().any(char::is_whitespace)
+        && value.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && local.len() <= 64
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        });
+    if !valid {
+        return Err(invalid("contact_email must be an email address"));
+    }
+    Ok(value.to_string())
+}
+
+/// An E.164 number. Spaces, dashes, dots and parentheses are dropped.
+pub fn contact_phone(value: &str) -> Result<String, ApiError> {
+    let number: String = value
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let valid = number.strip_prefix('+').is_some_and(|digits| {
+        (8..=15).contains(&digits.len())
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && !digits.starts_with('0')
+    });
+    if !valid {
+        return Err(invalid(
+            "contact_phone must be in international format like +40712345678",
+        ));
+    }
+    Ok(number)
+}
+
+fn invalid(message: &str) -> ApiError {
+    ApiError::BadRequest(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timezone_accepts_iana_zones() {
+        assert_eq!(timezone(" Europe/Bucharest ").unwrap(), "Europe/Bucharest");
+        assert_eq!(timezone("UTC").unwrap(), "UTC");
+        assert_eq!(timezone("America/New_York").unwrap(), "America/New_York");
+    }
+
+    #[test]
+    fn timezone_rejects_unknown_zones() {
+        assert!(timezone("Mars/Olympus_Mons").is_err());
+        assert!(timezone("").is_err());
+        assert!(timezone("+02:00").is_err());
+    }
+
+    #[test]
+    fn timezone_rejects_fractional_hour_offsets() {
+        assert!(timezone("Asia/Kolkata").is_err());
+        assert!(timezone("Asia/Kathmandu").is_err());
+        assert!(timezone("Australia/Adelaide").is_err());
+    }
+
+    #[test]
+    fn locale_normalizes_casing_and_separator() {
+        assert_eq!(locale("ro_ro").unwrap(), "ro-RO");
+        assert_eq!(locale("EN").unwrap(), "en");
+        assert_eq!(locale("zh-hant-tw").unwrap(), "zh-Hant-TW");
+        assert_eq!(locale("es-419").unwrap(), "es-419");
+    }
+
+    #[test]
+    fn locale_rejects_malformed_tags() {
+        assert!(locale("english").is_err());
+        assert!(locale("e").is_err());
+        assert!(locale("en-USA").is_err());
+        assert!(locale("en-US-extra").is_err());
+    }
+
+    #[test]
+    fn currency_accepts_active_codes_in_any_case() {
+        assert_eq!(currency("EUR").unwrap(), "EUR");
+        assert_eq!(currency(" usd ").unwrap(), "USD");
+        assert_eq!(currency("ron").unwrap(), "RON");
+    }
+
+    #[test]
+    fn currency_rejects_unknown_and_non_currency_codes() {
+        assert!(currency("ABC").is_err());
+        assert!(currency("EURO").is_err());
+        assert!(currency("").is_err());
+        // Precious metal and test codes are in ISO 4217 but not billable.
+        assert!(currency("XAU").is_err());
+        assert!(currency("XTS").is_err());
+    }
+
+    #[test]
+    fn currency_list_is_sorted_for_binary_search() {
+        assert!(CURRENCIES.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn tariff_ref_limits_length_and_characters() {
+        assert_eq!(tariff_ref(" ro:enel-2026.a ").unwrap(), "ro:enel-2026.a");
+        assert!(tariff_ref("").is_err());
+        assert!(tariff_ref("has space").is_err());
+        assert!(tariff_ref(&"a".repeat(MAX_TARIFF_REF_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn contact_email_requires_a_domain() {
+        assert_eq!(contact_email(" a@example.com ").unwrap(), "a@example.com");
+        assert!(contact_email("a@localhost").is_err());
+        assert!(contact_email("@example.com").is_err());
+        assert!(contact_email("a b@example.com").is_err());
+    }
+
+    #[test]
+    fn contact_phone_normalizes_to_e164() {
+        assert_eq!(contact_phone("+40 (712) 345-678").unwrap(), "+40712345678");
+        assert!(contact_phone("0712345678").is_err());
+        assert!(contact_phone("+0712345678").is_err());
+        assert!(contact_phone("+40").is_err());
+    }
+}
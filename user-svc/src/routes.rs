@@ -2,12 +2,17 @@ use std::sync::Arc;
 
 use axum::{
     Router,
+    middleware::from_fn_with_state,
     routing::{get, post, put},
 };
 
 use crate::{
     AppState,
-    handlers::{create, get_all, health_check, me, update},
+    handlers::{
+        create, create_household, delete_household, get_all, get_household, health_check,
+        list_households, me, put_member, remove_member, rename_household, update,
+    },
+    scopes,
 };
 
 pub fn create_route() -> Router<Arc<AppState>> {
@@ -15,6 +20,20 @@ pub fn create_route() -> Router<Arc<AppState>> {
         .route("/health", get(health_check))
         .route("/create", post(create))
         .route("/update", put(update))
-        .route("/get_all", get(get_all))
+        .route(
+            "/get_all",
+            get(get_all).route_layer(from_fn_with_state(scopes::USERS_ADMIN, scopes::require)),
+        )
         .route("/me", get(me))
+        .route("/households", get(list_households).post(create_household))
+        .route(
+            "/households/{id}",
+            get(get_household)
+                .put(rename_household)
+                .delete(delete_household),
+        )
+        .route(
+            "/households/{id}/members/{user_id}",
+            put(put_member).delete(remove_member),
+        )
 }
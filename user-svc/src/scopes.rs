@@ -0,0 +1,28 @@
+//! Permission scopes forwarded by auth-svc in `X-Scopes`.
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{errors::ApiError, handlers::AuthenticatedUser};
+
+/// Read the caller's own profile.
+pub const USERS_READ: &str = "users:read";
+/// Create and update the caller's own profile.
+pub const USERS_WRITE: &str = "users:write";
+/// Read and update every profile.
+pub const USERS_ADMIN: &str = "users:admin";
+
+/// Route layer rejecting principals without the scope it is built with:
+/// `route_layer(from_fn_with_state(scopes::USERS_ADMIN, scopes::require))`.
+pub async fn require(
+    State(scope): State<&'static str>,
+    user: AuthenticatedUser,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    user.require_scope(scope)?;
+    Ok(next.run(req).await)
+}
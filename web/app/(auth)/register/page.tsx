@@ -18,7 +18,7 @@ import { DASHBOARD_ROUTE } from "@/lib/constants";
 const registerSchema = z
   .object({
     username: z.string().min(1, "Username is required"),
-    password: z.string().min(6, "Password must be at least 6 characters"),
+    password: z.string().min(8, "Password must be at least 8 characters"),
     confirmPassword: z.string().min(6, "Confirm your password"),
   })
   .refine((data) => data.password === data.confirmPassword, {
@@ -1,3 +1,4 @@
+import Link from "next/link";
 import { redirect } from "next/navigation";
 
 import { AppShell } from "@/components/AppShell";
@@ -5,7 +6,7 @@ import { AdminConsole } from "@/components/admin/AdminConsole";
 import { Badge } from "@/components/Badge";
 import { deviceApi, userApi } from "@/lib/api";
 import { decodeAuthToken, hasAdminClaim } from "@/lib/auth";
-import { DASHBOARD_ROUTE } from "@/lib/constants";
+import { ADMIN_ROUTE, DASHBOARD_ROUTE } from "@/lib/constants";
 import { requireAuthToken, withAuthHandling } from "@/lib/server";
 import { logoutAction } from "../actions";
 import {
@@ -16,7 +17,12 @@ import {
   adminUpdateUserAction,
 } from "./actions";
 
-export default async function AdminPage() {
+type AdminPageProps = {
+  searchParams: Promise<{ cursor?: string }>;
+};
+
+export default async function AdminPage({ searchParams }: AdminPageProps) {
+  const { cursor } = await searchParams;
   const token = await requireAuthToken();
   const claims = decodeAuthToken(token);
 
@@ -24,8 +30,8 @@ export default async function AdminPage() {
     redirect(DASHBOARD_ROUTE);
   }
 
-  const [users, devices] = await withAuthHandling(() =>
-    Promise.all([userApi.getAll(token), deviceApi.readAll(token)]),
+  const [userPage, devices] = await withAuthHandling(() =>
+    Promise.all([userApi.getAll(token, cursor), deviceApi.readAll(token)]),
   );
 
   const userLabel = claims?.sub ? claims.sub.slice(0, 8) : "Admin";
@@ -40,11 +46,11 @@ export default async function AdminPage() {
               Manage user profiles and devices across the platform.
             </p>
           </div>
-          <Badge variant="muted">{users.length} users · {devices.length} devices</Badge>
+          <Badge variant="muted">{userPage.total} users · {devices.length} devices</Badge>
         </div>
 
         <AdminConsole
-          users={users}
+          users={userPage.items}
           devices={devices}
           actions={{
             updateUser: adminUpdateUserAction,
@@ -54,6 +60,24 @@ export default async function AdminPage() {
             deleteAllDevices: adminDeleteAllDevicesAction,
           }}
         />
+
+        {(cursor || userPage.next_cursor) && (
+          <nav className="flex items-center justify-end gap-4 text-sm">
+            {cursor && (
+              <Link className="font-semibold text-white hover:text-neutral-200" href={ADMIN_ROUTE}>
+                First page
+              </Link>
+            )}
+            {userPage.next_cursor && (
+              <Link
+                className="font-semibold text-white hover:text-neutral-200"
+                href={`${ADMIN_ROUTE}?cursor=${encodeURIComponent(userPage.next_cursor)}`}
+              >
+                Next page
+              </Link>
+            )}
+          </nav>
+        )}
       </section>
     </AppShell>
   );
@@ -10,6 +10,7 @@ import type {
   RegisterRequest,
   User,
   UserCreateRequest,
+  UserPage,
   UserUpdateRequest,
 } from "@/lib/types";
 
@@ -116,10 +117,13 @@ export const userApi = {
       body: payload,
       token,
     }),
-  getAll: (token: string) =>
-    fetchJSON<User[]>("/user/get_all", {
-      token,
-    }),
+  getAll: (token: string, cursor?: string) =>
+    fetchJSON<UserPage>(
+      cursor ? `/user/get_all?cursor=${encodeURIComponent(cursor)}` : "/user/get_all",
+      {
+        token,
+      },
+    ),
 };
 
 export const deviceApi = {
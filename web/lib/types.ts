@@ -9,10 +9,22 @@ export interface User {
   unit_energy: UnitEnergy;
   home_type: HomeType;
   goal_kwh_month: number;
+  timezone: string;
+  locale: string;
+  currency: string;
+  tariff_ref: string | null;
+  contact_email: string | null;
+  contact_phone: string | null;
   created_at: string;
   updated_at: string;
 }
 
+export interface UserPage {
+  items: User[];
+  total: number;
+  next_cursor: string | null;
+}
+
 export interface Device {
   id: UUID;
   name: string;
@@ -29,6 +41,9 @@ export interface HourlyPoint {
 export interface ConsumptionResponse {
   device_id: UUID;
   day: string;
+  timezone: string;
+  currency: string | null;
+  tariff_ref: string | null;
   points: HourlyPoint[];
 }
 
@@ -52,6 +67,12 @@ export interface UserCreateRequest {
   unit_energy: UnitEnergy;
   home_type: HomeType;
   goal_kwh_month: number;
+  timezone?: string;
+  locale?: string;
+  currency?: string;
+  tariff_ref?: string;
+  contact_email?: string;
+  contact_phone?: string;
 }
 
 export interface UserUpdateRequest {
@@ -59,6 +80,12 @@ export interface UserUpdateRequest {
   unit_energy?: UnitEnergy;
   home_type?: HomeType;
   goal_kwh_month?: number;
+  timezone?: string;
+  locale?: string;
+  currency?: string;
+  tariff_ref?: string | null;
+  contact_email?: string | null;
+  contact_phone?: string | null;
 }
 
 export interface DeviceCreateRequest {